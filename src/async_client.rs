@@ -0,0 +1,405 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use async_io::Timer;
+use futures_lite::{future, Stream, StreamExt};
+use nats::asynk::{Connection, Message, Subscription};
+use serde::de::DeserializeOwned;
+use wascap::prelude::*;
+
+use crate::auction::{retain_eligible, BidCollector};
+use crate::controlplane::{
+    LatticeSubjects, LaunchAck, LaunchAuctionRequest, LaunchAuctionResponse, LaunchCommand,
+    LaunchProviderCommand, ProviderAuctionRequest, ProviderAuctionResponse, ProviderLaunchAck,
+    TerminateCommand, TerminateProviderCommand,
+};
+use crate::events::decode_event;
+use crate::inventory::{aggregate, decode_reply};
+use crate::{
+    AuctionOptions, Binding, BusEvent, CloudEvent, ConstraintSet, EventDecodeError, EventFilter,
+    HostProfile, HostedCapability, InventoryReply, InventoryReport, InventoryResponse,
+    LatticeError, Result, EVENTS, INVENTORY_ACTORS, INVENTORY_BINDINGS, INVENTORY_CAPABILITIES,
+    INVENTORY_HOSTS,
+};
+
+/// An asynchronous client for interacting with the lattice. This client mirrors the operations
+/// of the blocking [Client](struct.Client.html), but never blocks the calling thread while
+/// waiting on probe replies or auction bids, so it can be used freely from within any async
+/// runtime. Only available with the `async` feature enabled
+pub struct AsyncClient {
+    nc: Connection,
+    namespace: Option<String>,
+    timeout: Duration,
+}
+
+impl AsyncClient {
+    /// Creates a new asynchronous lattice client from an existing asynchronous NATS connection
+    pub fn with_connection(
+        nc: Connection,
+        call_timeout: Duration,
+        namespace: Option<String>,
+    ) -> Self {
+        AsyncClient {
+            nc,
+            timeout: call_timeout,
+            namespace,
+        }
+    }
+
+    /// Retrieves the list of all hosts running within the lattice. If it takes a host longer
+    /// than the call timeout period to reply to the probe, it will not be included in the list
+    /// of hosts. Replies that cannot be decoded are recorded in the report's errors rather than
+    /// failing the whole probe
+    pub async fn get_hosts(&self) -> Result<InventoryReport<Vec<HostProfile>>> {
+        let mut report: InventoryReport<Vec<HostProfile>> = InventoryReport::default();
+        for reply in self.probe_inventory(INVENTORY_HOSTS).await? {
+            match reply {
+                Ok(InventoryResponse::Host(h)) => report.items.push(h),
+                Ok(_) => {}
+                Err(e) => report.errors.push(e),
+            }
+        }
+        Ok(report)
+    }
+
+    /// Retrieves a list of all bindings from actors to capabilities within the lattice (provided
+    /// the host responds to the probe within the client timeout period)
+    pub async fn get_bindings(&self) -> Result<InventoryReport<HashMap<String, Vec<Binding>>>> {
+        let replies = self.probe_inventory(INVENTORY_BINDINGS).await?;
+        Ok(aggregate(replies.into_iter().filter_map(
+            |reply| match reply {
+                Ok(InventoryResponse::Bindings { host, bindings }) => Some(Ok((host, bindings))),
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            },
+        )))
+    }
+
+    /// Retrieves the list of all actors currently running within the lattice (as discovered within
+    /// the client timeout period)
+    pub async fn get_actors(&self) -> Result<InventoryReport<HashMap<String, Vec<Claims<Actor>>>>> {
+        let replies = self.probe_inventory(INVENTORY_ACTORS).await?;
+        Ok(aggregate(replies.into_iter().filter_map(
+            |reply| match reply {
+                Ok(InventoryResponse::Actors { host, actors }) => Some(Ok((host, actors))),
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            },
+        )))
+    }
+
+    /// Retrieves the list of all capabilities within the lattice (discovery limited by the client timeout period)
+    pub async fn get_capabilities(
+        &self,
+    ) -> Result<InventoryReport<HashMap<String, Vec<HostedCapability>>>> {
+        let replies = self.probe_inventory(INVENTORY_CAPABILITIES).await?;
+        Ok(aggregate(replies.into_iter().filter_map(
+            |reply| match reply {
+                Ok(InventoryResponse::Capabilities { host, capabilities }) => {
+                    Some(Ok((host, capabilities)))
+                }
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            },
+        )))
+    }
+
+    /// Watches the lattice for bus events, returning a stream of the events as they arrive. Dropping
+    /// the stream cancels the underlying subscription. Messages that cannot be decoded are yielded
+    /// as an `EventDecodeError` rather than ending the stream
+    pub async fn watch_events(
+        &self,
+    ) -> Result<impl Stream<Item = std::result::Result<BusEvent, EventDecodeError>>> {
+        self.watch_events_filtered(EventFilter::new()).await
+    }
+
+    /// Watches the lattice for bus events that satisfy the given filter. Every event is still received
+    /// from the NATS server, and the filter is applied client-side as the stream is polled. Messages that
+    /// cannot be decoded are always yielded as an `EventDecodeError`
+    pub async fn watch_events_filtered(
+        &self,
+        filter: EventFilter,
+    ) -> Result<impl Stream<Item = std::result::Result<BusEvent, EventDecodeError>>> {
+        let sub = self.nc.subscribe(&self.gen_subject(EVENTS)).await?;
+        Ok(sub
+            .map(|msg| decode_event(&msg.data))
+            .filter(move |decoded| match decoded {
+                Ok(event) => filter.matches(event),
+                Err(_) => true,
+            }))
+    }
+
+    /// Publishes an event onto the lattice bus, wrapped in a CloudEvent envelope using the legacy data format
+    /// understood by all lattice consumers
+    pub async fn publish_event(&self, event: BusEvent) -> Result<()> {
+        self.publish_cloud_event(&CloudEvent::from(event)).await
+    }
+
+    /// Publishes a CloudEvent envelope onto the lattice bus. Returns `LatticeError::InvalidEvent` without
+    /// publishing if the envelope does not pass validation
+    pub async fn publish_cloud_event(&self, event: &CloudEvent) -> Result<()> {
+        event.validate()?;
+        self.nc
+            .publish(&self.gen_subject(EVENTS), &serde_json::to_vec(event)?)
+            .await?;
+        let _ = self.nc.flush().await;
+        Ok(())
+    }
+
+    /// Performs an auction among all hosts on the lattice, requesting that the given actor be launched (loaded+started)
+    /// on a suitable host as described by the set of constraints. Only hosts that believe they can launch the actor
+    /// will reply. The actor to be launched is identified by an OCI registry reference. If no hosts bid on the
+    /// auction, this returns `LatticeError::EmptyAuction`
+    pub async fn perform_actor_launch_auction(
+        &self,
+        actor_id: &str,
+        constraints: HashMap<String, String>,
+    ) -> Result<Vec<LaunchAuctionResponse>> {
+        self.perform_actor_launch_auction_with(
+            actor_id,
+            &constraints.into(),
+            &AuctionOptions::default(),
+        )
+        .await
+    }
+
+    /// Performs an actor launch auction with the given [ConstraintSet](struct.ConstraintSet.html), collecting
+    /// bids as described by the given [AuctionOptions](struct.AuctionOptions.html). Constraints that hosts
+    /// cannot evaluate themselves are evaluated against the labels reported by `get_hosts`
+    pub async fn perform_actor_launch_auction_with(
+        &self,
+        actor_id: &str,
+        constraints: &ConstraintSet,
+        options: &AuctionOptions,
+    ) -> Result<Vec<LaunchAuctionResponse>> {
+        let mut req = LaunchAuctionRequest::new(actor_id, constraints.equality_subset()?);
+        req.expressions = constraints.expressions();
+        let bids = self
+            .collect_bids(
+                &self.gen_auction_subject(),
+                &serde_json::to_vec(&req)?,
+                options,
+            )
+            .await?;
+        self.verify_bids(bids, constraints, |b: &LaunchAuctionResponse| &b.host_id)
+            .await
+    }
+
+    /// Performs an auction among all hosts on the lattice, requesting that the given capability provider
+    /// (indicated by OCI image reference) be loaded/started. If no hosts bid on the auction, this
+    /// returns `LatticeError::EmptyAuction`
+    pub async fn perform_provider_launch_auction(
+        &self,
+        provider_ref: &str,
+        binding_name: &str,
+        constraints: HashMap<String, String>,
+    ) -> Result<Vec<ProviderAuctionResponse>> {
+        self.perform_provider_launch_auction_with(
+            provider_ref,
+            binding_name,
+            &constraints.into(),
+            &AuctionOptions::default(),
+        )
+        .await
+    }
+
+    /// Performs a provider launch auction with the given [ConstraintSet](struct.ConstraintSet.html), collecting
+    /// bids as described by the given [AuctionOptions](struct.AuctionOptions.html)
+    pub async fn perform_provider_launch_auction_with(
+        &self,
+        provider_ref: &str,
+        binding_name: &str,
+        constraints: &ConstraintSet,
+        options: &AuctionOptions,
+    ) -> Result<Vec<ProviderAuctionResponse>> {
+        let mut req =
+            ProviderAuctionRequest::new(provider_ref, binding_name, constraints.equality_subset()?);
+        req.expressions = constraints.expressions();
+        let bids = self
+            .collect_bids(
+                &self.gen_provider_auction_subject(),
+                &serde_json::to_vec(&req)?,
+                options,
+            )
+            .await?;
+        self.verify_bids(bids, constraints, |b: &ProviderAuctionResponse| &b.host_id)
+            .await
+    }
+
+    /// Retrieves the hosts within the lattice whose labels satisfy the given constraints
+    pub async fn hosts_matching(&self, constraints: &ConstraintSet) -> Result<Vec<HostProfile>> {
+        Ok(constraints.matching_hosts(self.get_hosts().await?.items))
+    }
+
+    /// Tells the given host to launch the provider, confirming only receipt of the launch request.
+    /// An acknowledgement that does not match the request results in a `LatticeError::UnexpectedAck`
+    pub async fn launch_provider_on_host(
+        &self,
+        provider_ref: &str,
+        host_id: &str,
+        binding_name: &str,
+    ) -> Result<ProviderLaunchAck> {
+        let msg = LaunchProviderCommand {
+            provider_ref: provider_ref.to_string(),
+            binding_name: binding_name.to_string(),
+        };
+        let reply = self
+            .request(
+                &self.gen_launch_provider_subject(host_id),
+                &serde_json::to_vec(&msg)?,
+            )
+            .await?;
+        let ack: ProviderLaunchAck = serde_json::from_slice(&reply.data)?;
+        if ack.provider_ref != provider_ref || ack.host != host_id {
+            return Err(LatticeError::UnexpectedAck(format!("{:?}", ack)));
+        }
+        Ok(ack)
+    }
+
+    /// Tells the given host to launch the actor, confirming only that the target host acknowledged
+    /// the request. An acknowledgement that does not match the request results in a
+    /// `LatticeError::UnexpectedAck`
+    pub async fn launch_actor_on_host(&self, actor_id: &str, host_id: &str) -> Result<LaunchAck> {
+        let msg = LaunchCommand {
+            actor_id: actor_id.to_string(),
+        };
+        let reply = self
+            .request(
+                &self.gen_launch_actor_subject(host_id),
+                &serde_json::to_vec(&msg)?,
+            )
+            .await?;
+        let ack: LaunchAck = serde_json::from_slice(&reply.data)?;
+        if ack.actor_id != actor_id || ack.host != host_id {
+            return Err(LatticeError::UnexpectedAck(format!("{:?}", ack)));
+        }
+        Ok(ack)
+    }
+
+    /// Sends a command to the specified host telling it to terminate an actor. The success of this command indicates
+    /// a successful publication, and not necessarily a successful remote actor termination
+    pub async fn stop_actor_on_host(&self, actor_id: &str, host_id: &str) -> Result<()> {
+        let msg = TerminateCommand {
+            actor_id: actor_id.to_string(),
+        };
+        self.nc
+            .publish(
+                &self.gen_terminate_actor_subject(host_id),
+                &serde_json::to_vec(&msg)?,
+            )
+            .await?;
+        let _ = self.nc.flush().await;
+        Ok(())
+    }
+
+    /// Sends a command to the specified host telling it to terminate a capability provider. The success of this
+    /// command indicates a successful publication, and not necessarily a successful remote provider termination
+    pub async fn stop_provider_on_host(
+        &self,
+        provider_ref: &str,
+        binding_name: &str,
+        host_id: &str,
+    ) -> Result<()> {
+        let msg = TerminateProviderCommand::new(provider_ref, binding_name);
+        self.nc
+            .publish(
+                &self.gen_terminate_provider_subject(host_id),
+                &serde_json::to_vec(&msg)?,
+            )
+            .await?;
+        let _ = self.nc.flush().await;
+        Ok(())
+    }
+
+    async fn probe_inventory(
+        &self,
+        subject: &str,
+    ) -> Result<Vec<InventoryReply<InventoryResponse>>> {
+        Ok(self
+            .collect_replies(&self.gen_subject(subject), &[], self.timeout)
+            .await?
+            .iter()
+            .map(|msg| decode_reply(&msg.subject, &msg.data))
+            .collect())
+    }
+
+    async fn collect_bids<T: DeserializeOwned>(
+        &self,
+        subject: &str,
+        payload: &[u8],
+        options: &AuctionOptions,
+    ) -> Result<Vec<T>> {
+        let mut sub = self.nc.request_multi(subject, payload).await?;
+        let mut collector = BidCollector::open(options);
+        while let Some(wait) = collector.next_wait() {
+            match next_before(&mut sub, Instant::now() + wait).await {
+                Some(msg) => collector.push(&msg.data)?,
+                None => break,
+            }
+        }
+        collector.finish()
+    }
+
+    async fn verify_bids<T>(
+        &self,
+        bids: Vec<T>,
+        constraints: &ConstraintSet,
+        host_id: impl Fn(&T) -> &String,
+    ) -> Result<Vec<T>> {
+        if !constraints.requires_client_evaluation() {
+            return Ok(bids);
+        }
+        let eligible: Vec<String> = self
+            .hosts_matching(constraints)
+            .await?
+            .into_iter()
+            .map(|h| h.id)
+            .collect();
+        retain_eligible(bids, &eligible, host_id)
+    }
+
+    async fn collect_replies(
+        &self,
+        subject: &str,
+        payload: &[u8],
+        window: Duration,
+    ) -> Result<Vec<Message>> {
+        let mut sub = self.nc.request_multi(subject, payload).await?;
+        let deadline = Instant::now() + window;
+        let mut replies = vec![];
+        while let Some(msg) = next_before(&mut sub, deadline).await {
+            replies.push(msg);
+        }
+        Ok(replies)
+    }
+
+    async fn request(&self, subject: &str, payload: &[u8]) -> Result<Message> {
+        let deadline = Instant::now() + self.timeout;
+        future::or(
+            async { Some(self.nc.request(subject, payload).await) },
+            async {
+                Timer::at(deadline).await;
+                None
+            },
+        )
+        .await
+        .ok_or(LatticeError::Timeout)?
+        .map_err(LatticeError::from)
+    }
+}
+
+impl LatticeSubjects for AsyncClient {
+    fn namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+}
+
+async fn next_before(sub: &mut Subscription, deadline: Instant) -> Option<Message> {
+    future::or(sub.next(), async {
+        Timer::at(deadline).await;
+        None
+    })
+    .await
+}
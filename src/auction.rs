@@ -0,0 +1,216 @@
+use std::time::{Duration, Instant};
+
+use serde::de::DeserializeOwned;
+
+use crate::{LatticeError, Result, AUCTION_TIMEOUT_SECONDS};
+
+/// Controls how long a launch auction collects bids. By default an auction collects every bid that
+/// arrives within a 5 second window. The window is an upper bound: an auction can be configured to
+/// close as soon as it has received enough bids
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuctionOptions {
+    pub(crate) window: Duration,
+    max_bids: Option<usize>,
+    known_hosts: Option<usize>,
+}
+
+impl Default for AuctionOptions {
+    fn default() -> Self {
+        AuctionOptions {
+            window: Duration::from_secs(AUCTION_TIMEOUT_SECONDS),
+            max_bids: None,
+            known_hosts: None,
+        }
+    }
+}
+
+impl AuctionOptions {
+    /// Creates a new set of auction options with the default window and no early termination
+    pub fn new() -> AuctionOptions {
+        AuctionOptions::default()
+    }
+
+    /// Sets the maximum length of time the auction will wait for bids
+    pub fn window(mut self, window: Duration) -> AuctionOptions {
+        self.window = window;
+        self
+    }
+
+    /// Closes the auction as soon as the given number of bids have been received. Zero leaves the
+    /// auction open for the full window
+    pub fn max_bids(mut self, max_bids: usize) -> AuctionOptions {
+        self.max_bids = Some(max_bids).filter(|n| *n > 0);
+        self
+    }
+
+    /// Closes the auction as soon as the first bid is received, making the first host to respond the
+    /// only candidate
+    pub fn first_responder(self) -> AuctionOptions {
+        self.max_bids(1)
+    }
+
+    /// Closes the auction as soon as every known host has bid, where the number of known hosts is
+    /// typically obtained from a prior call to `get_hosts`. Hosts that cannot satisfy the auction do not
+    /// bid, so auctions that some hosts decline still run for the full window. A count of zero, as
+    /// produced by a host probe that received no replies, leaves the auction open for the full window
+    pub fn known_hosts(mut self, count: usize) -> AuctionOptions {
+        self.known_hosts = Some(count).filter(|n| *n > 0);
+        self
+    }
+
+    /// Indicates whether an auction that has received the given number of bids can close early
+    pub(crate) fn is_satisfied(&self, bids: usize) -> bool {
+        self.max_bids.is_some_and(|max| bids >= max)
+            || self.known_hosts.is_some_and(|count| bids >= count)
+    }
+}
+
+/// Collects the bids of a single auction, deciding when the auction closes. Shared by the blocking and
+/// asynchronous clients so that the same options close an auction at the same time in both: the window
+/// is measured from the moment the auction opens, and the auction closes early once the options are
+/// satisfied
+pub(crate) struct BidCollector<'a, T> {
+    options: &'a AuctionOptions,
+    deadline: Instant,
+    bids: Vec<T>,
+}
+
+impl<'a, T: DeserializeOwned> BidCollector<'a, T> {
+    /// Opens the auction window
+    pub(crate) fn open(options: &'a AuctionOptions) -> BidCollector<'a, T> {
+        BidCollector {
+            options,
+            deadline: Instant::now() + options.window,
+            bids: vec![],
+        }
+    }
+
+    /// Returns how long to wait for the next bid, or `None` if the auction has closed
+    pub(crate) fn next_wait(&self) -> Option<Duration> {
+        if self.options.is_satisfied(self.bids.len()) {
+            return None;
+        }
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining == Duration::from_secs(0) {
+            None
+        } else {
+            Some(remaining)
+        }
+    }
+
+    /// Records a bid from its raw payload
+    pub(crate) fn push(&mut self, data: &[u8]) -> Result<()> {
+        self.bids.push(serde_json::from_slice(data)?);
+        Ok(())
+    }
+
+    /// Closes the auction, returning `LatticeError::EmptyAuction` if no bids were received
+    pub(crate) fn finish(self) -> Result<Vec<T>> {
+        if self.bids.is_empty() {
+            Err(LatticeError::EmptyAuction)
+        } else {
+            Ok(self.bids)
+        }
+    }
+}
+
+/// Discards the bids from hosts that are not among the eligible hosts, as determined by evaluating the
+/// auction's constraints on the client. Returns `LatticeError::EmptyAuction` if no bids remain
+pub(crate) fn retain_eligible<T>(
+    bids: Vec<T>,
+    eligible: &[String],
+    host_id: impl Fn(&T) -> &String,
+) -> Result<Vec<T>> {
+    let bids: Vec<T> = bids
+        .into_iter()
+        .filter(|b| eligible.contains(host_id(b)))
+        .collect();
+    if bids.is_empty() {
+        Err(LatticeError::EmptyAuction)
+    } else {
+        Ok(bids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_options_wait_for_the_full_window() {
+        let options = AuctionOptions::default();
+        assert_eq!(options.window, Duration::from_secs(AUCTION_TIMEOUT_SECONDS));
+        assert!(!options.is_satisfied(0));
+        assert!(!options.is_satisfied(100));
+    }
+
+    #[test]
+    fn max_bids_closes_once_reached() {
+        let options = AuctionOptions::new().max_bids(2);
+        assert!(!options.is_satisfied(1));
+        assert!(options.is_satisfied(2));
+        assert!(options.is_satisfied(3));
+    }
+
+    #[test]
+    fn first_responder_closes_on_the_first_bid() {
+        let options = AuctionOptions::new().first_responder();
+        assert!(!options.is_satisfied(0));
+        assert!(options.is_satisfied(1));
+    }
+
+    #[test]
+    fn known_hosts_closes_once_every_host_has_bid() {
+        let options = AuctionOptions::new().known_hosts(3);
+        assert!(!options.is_satisfied(2));
+        assert!(options.is_satisfied(3));
+    }
+
+    #[test]
+    fn zero_counts_are_treated_as_unset() {
+        assert!(!AuctionOptions::new().max_bids(0).is_satisfied(0));
+        assert!(!AuctionOptions::new().known_hosts(0).is_satisfied(0));
+        assert_eq!(AuctionOptions::new().max_bids(0), AuctionOptions::new());
+    }
+
+    #[test]
+    fn collector_closes_once_satisfied() {
+        let options = AuctionOptions::new().max_bids(1);
+        let mut collector: BidCollector<'_, String> = BidCollector::open(&options);
+        assert!(collector.next_wait().is_some());
+        collector.push(b"\"bid\"").unwrap();
+        assert!(collector.next_wait().is_none());
+        assert_eq!(collector.finish().unwrap(), vec!["bid".to_string()]);
+    }
+
+    #[test]
+    fn collector_without_bids_is_an_empty_auction() {
+        let options = AuctionOptions::new().window(Duration::from_millis(0));
+        let collector: BidCollector<'_, String> = BidCollector::open(&options);
+        assert!(collector.next_wait().is_none());
+        assert!(matches!(
+            collector.finish(),
+            Err(LatticeError::EmptyAuction)
+        ));
+    }
+
+    fn hosts(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn ineligible_bids_are_discarded() {
+        let bids = hosts(&["Na", "Nb", "Nc"]);
+        let kept = retain_eligible(bids, &hosts(&["Nc", "Na"]), |b| b).unwrap();
+        assert_eq!(kept, hosts(&["Na", "Nc"]));
+    }
+
+    #[test]
+    fn no_eligible_bids_is_an_empty_auction() {
+        let bids = hosts(&["Na"]);
+        assert!(matches!(
+            retain_eligible(bids, &hosts(&["Nb"]), |b| b),
+            Err(LatticeError::EmptyAuction)
+        ));
+    }
+}
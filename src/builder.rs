@@ -0,0 +1,256 @@
+use std::{io, path::PathBuf, time::Duration};
+
+#[cfg(feature = "async")]
+use crate::AsyncClient;
+use crate::{Client, LatticeError, Result};
+
+const DEFAULT_SERVER: &str = "127.0.0.1";
+const DEFAULT_CONNECTION_NAME: &str = "waSCC Lattice";
+const DEFAULT_CALL_TIMEOUT_MILLIS: u64 = 600;
+
+type ConnectionCallback = Box<dyn Fn() + Send + Sync + 'static>;
+
+enum Authentication {
+    Anonymous,
+    Credentials(PathBuf),
+    UserPass(String, String),
+    Token(String),
+    NKeySeed(String),
+}
+
+/// A builder used to configure the NATS connection options and call behavior of a
+/// lattice [Client](struct.Client.html). Only one authentication method can be in effect,
+/// so the last one supplied to the builder wins
+pub struct ClientBuilder {
+    servers: Vec<String>,
+    auth: Authentication,
+    client_cert: Option<(PathBuf, PathBuf)>,
+    root_certificates: Vec<PathBuf>,
+    name: String,
+    reconnect_callback: Option<ConnectionCallback>,
+    disconnect_callback: Option<ConnectionCallback>,
+    call_timeout: Duration,
+    namespace: Option<String>,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        ClientBuilder {
+            servers: vec![],
+            auth: Authentication::Anonymous,
+            client_cert: None,
+            root_certificates: vec![],
+            name: DEFAULT_CONNECTION_NAME.to_string(),
+            reconnect_callback: None,
+            disconnect_callback: None,
+            call_timeout: Duration::from_millis(DEFAULT_CALL_TIMEOUT_MILLIS),
+            namespace: None,
+        }
+    }
+}
+
+impl ClientBuilder {
+    /// Creates a new builder that will connect anonymously to a NATS server on the local host
+    pub fn new() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    /// Adds a seed server to the list of NATS servers used to connect to the lattice. If no
+    /// servers are supplied, the client will connect to `127.0.0.1`
+    pub fn server(mut self, url: &str) -> ClientBuilder {
+        self.servers.push(url.to_string());
+        self
+    }
+
+    /// Adds multiple seed servers to the list of NATS servers used to connect to the lattice
+    pub fn servers<S: AsRef<str>>(mut self, urls: &[S]) -> ClientBuilder {
+        self.servers
+            .extend(urls.iter().map(|u| u.as_ref().to_string()));
+        self
+    }
+
+    /// Authenticate using a NATS credentials (JWT) file
+    pub fn credentials(mut self, path: impl Into<PathBuf>) -> ClientBuilder {
+        self.auth = Authentication::Credentials(path.into());
+        self
+    }
+
+    /// Authenticate using a user name and password
+    pub fn user_password(mut self, user: &str, password: &str) -> ClientBuilder {
+        self.auth = Authentication::UserPass(user.to_string(), password.to_string());
+        self
+    }
+
+    /// Authenticate using a token
+    pub fn token(mut self, token: &str) -> ClientBuilder {
+        self.auth = Authentication::Token(token.to_string());
+        self
+    }
+
+    /// Authenticate using an nkey, supplied as the seed (private key) of a user key pair
+    pub fn nkey_seed(mut self, seed: &str) -> ClientBuilder {
+        self.auth = Authentication::NKeySeed(seed.to_string());
+        self
+    }
+
+    /// Supplies the certificate and private key files used for TLS client authentication
+    pub fn client_cert(
+        mut self,
+        cert: impl Into<PathBuf>,
+        key: impl Into<PathBuf>,
+    ) -> ClientBuilder {
+        self.client_cert = Some((cert.into(), key.into()));
+        self
+    }
+
+    /// Adds a root certificate file used to verify the NATS server's TLS certificate
+    pub fn root_certificate(mut self, path: impl Into<PathBuf>) -> ClientBuilder {
+        self.root_certificates.push(path.into());
+        self
+    }
+
+    /// Sets the name of the connection as it appears to the NATS server
+    pub fn connection_name(mut self, name: &str) -> ClientBuilder {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Sets a callback to be invoked when connectivity to a server has been re-established
+    pub fn reconnect_callback<F>(mut self, cb: F) -> ClientBuilder
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.reconnect_callback = Some(Box::new(cb));
+        self
+    }
+
+    /// Sets a callback to be invoked when connectivity to the server has been lost
+    pub fn disconnect_callback<F>(mut self, cb: F) -> ClientBuilder
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.disconnect_callback = Some(Box::new(cb));
+        self
+    }
+
+    /// Sets the period of time the client will wait for replies to lattice requests and probes
+    pub fn call_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.call_timeout = timeout;
+        self
+    }
+
+    /// Sets the lattice namespace used to prefix all subjects
+    pub fn namespace(mut self, namespace: &str) -> ClientBuilder {
+        self.namespace = Some(namespace.to_string());
+        self
+    }
+
+    /// Connects to the lattice, returning `LatticeError::Connection` if the connection cannot
+    /// be established or the connection options are invalid
+    pub fn build(self) -> Result<Client> {
+        let (opts, servers, call_timeout, namespace) = self.into_options()?;
+        let nc = opts.connect(&servers).map_err(LatticeError::Connection)?;
+        Ok(Client::with_connection(nc, call_timeout, namespace))
+    }
+
+    /// Asynchronously connects to the lattice, producing an [AsyncClient](struct.AsyncClient.html).
+    /// Returns `LatticeError::Connection` if the connection cannot be established or the
+    /// connection options are invalid
+    #[cfg(feature = "async")]
+    pub async fn build_async(self) -> Result<AsyncClient> {
+        let (opts, servers, call_timeout, namespace) = self.into_options()?;
+        let nc = opts
+            .connect_async(&servers)
+            .await
+            .map_err(LatticeError::Connection)?;
+        Ok(AsyncClient::with_connection(nc, call_timeout, namespace))
+    }
+
+    fn into_options(self) -> Result<(nats::Options, String, Duration, Option<String>)> {
+        let mut opts = match self.auth {
+            Authentication::Anonymous => nats::Options::new(),
+            Authentication::Credentials(path) => nats::Options::with_credentials(path),
+            Authentication::UserPass(user, password) => {
+                nats::Options::with_user_pass(&user, &password)
+            }
+            Authentication::Token(token) => nats::Options::with_token(&token),
+            Authentication::NKeySeed(seed) => {
+                let kp = user_key_pair(&seed)?;
+                nats::Options::with_nkey(&kp.public_key(), move |nonce| {
+                    // The NATS client offers no way to fail the handshake from here, so the failure is
+                    // logged to explain the authorization error the server will report
+                    kp.sign(nonce).unwrap_or_else(|e| {
+                        log::error!("Failed to sign the NATS connection nonce: {}", e.kind());
+                        vec![]
+                    })
+                })
+            }
+        };
+        if let Some((cert, key)) = self.client_cert {
+            opts = opts.client_cert(cert, key);
+        }
+        for root in self.root_certificates {
+            opts = opts.add_root_certificate(root);
+        }
+        if let Some(cb) = self.reconnect_callback {
+            opts = opts.reconnect_callback(cb);
+        }
+        if let Some(cb) = self.disconnect_callback {
+            opts = opts.disconnect_callback(cb);
+        }
+        opts = opts.with_name(&self.name);
+
+        let servers = if self.servers.is_empty() {
+            DEFAULT_SERVER.to_string()
+        } else {
+            self.servers.join(",")
+        };
+        Ok((opts, servers, self.call_timeout, self.namespace))
+    }
+}
+
+/// Parses the seed of a user nkey, checking that it can sign so that a bad seed is reported
+/// before connecting rather than as an opaque authorization failure
+fn user_key_pair(seed: &str) -> Result<nkeys::KeyPair> {
+    let invalid = |reason: String| {
+        LatticeError::Connection(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid nkey seed: {}", reason),
+        ))
+    };
+    let kp = nkeys::KeyPair::from_seed(seed).map_err(|e| invalid(e.kind().to_string()))?;
+    if !kp.public_key().starts_with('U') {
+        return Err(invalid("not the seed of a user key".to_string()));
+    }
+    kp.sign(b"lattice")
+        .map_err(|e| invalid(format!("unable to sign: {}", e.kind())))?;
+    Ok(kp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_seed_is_accepted() {
+        let seed = nkeys::KeyPair::new_user().seed().unwrap();
+        assert!(user_key_pair(&seed).is_ok());
+    }
+
+    #[test]
+    fn malformed_seed_is_rejected() {
+        assert!(matches!(
+            user_key_pair("SUNOTASEED"),
+            Err(LatticeError::Connection(_))
+        ));
+    }
+
+    #[test]
+    fn non_user_seed_is_rejected() {
+        let seed = nkeys::KeyPair::new_account().seed().unwrap();
+        assert!(matches!(
+            user_key_pair(&seed),
+            Err(LatticeError::Connection(_))
+        ));
+    }
+}
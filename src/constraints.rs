@@ -0,0 +1,519 @@
+use std::{collections::HashMap, fmt, iter::FromIterator, str::FromStr};
+
+use crate::{HostProfile, LatticeError};
+
+/// A single placement constraint, evaluated against the labels of a host. Constraints are written as:
+///
+/// * `label=value` (or `label==value`) and `label!=value`
+/// * `label in (a,b)` and `label notin (a,b)`
+/// * `exists(label)` and `!exists(label)`
+/// * `label>n`, `label>=n`, `label<n` and `label<=n`, which only match labels with numeric values
+///
+/// Negative constraints (`!=`, `notin`) are also satisfied by hosts that do not have the label at all
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constraint {
+    Equals(String, String),
+    NotEquals(String, String),
+    In(String, Vec<String>),
+    NotIn(String, Vec<String>),
+    Exists(String),
+    NotExists(String),
+    GreaterThan(String, f64),
+    GreaterOrEqual(String, f64),
+    LessThan(String, f64),
+    LessOrEqual(String, f64),
+}
+
+impl Constraint {
+    /// The label the constraint applies to
+    pub fn label(&self) -> &str {
+        use Constraint::*;
+
+        match self {
+            Equals(l, _) | NotEquals(l, _) | In(l, _) | NotIn(l, _) => l,
+            Exists(l) | NotExists(l) => l,
+            GreaterThan(l, _) | GreaterOrEqual(l, _) | LessThan(l, _) | LessOrEqual(l, _) => l,
+        }
+    }
+
+    /// Indicates whether the constraint is a plain equality match, the only kind of constraint
+    /// understood by all hosts
+    pub fn is_equality(&self) -> bool {
+        matches!(self, Constraint::Equals(..))
+    }
+
+    /// Indicates whether a host with the given labels satisfies the constraint
+    pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        use Constraint::*;
+
+        let value = labels.get(self.label());
+        let number = value.and_then(|v| v.trim().parse::<f64>().ok());
+        match self {
+            Equals(_, v) => value == Some(v),
+            NotEquals(_, v) => value != Some(v),
+            In(_, vs) => value.is_some_and(|value| vs.contains(value)),
+            NotIn(_, vs) => !value.is_some_and(|value| vs.contains(value)),
+            Exists(_) => value.is_some(),
+            NotExists(_) => value.is_none(),
+            GreaterThan(_, n) => number.is_some_and(|v| v > *n),
+            GreaterOrEqual(_, n) => number.is_some_and(|v| v >= *n),
+            LessThan(_, n) => number.is_some_and(|v| v < *n),
+            LessOrEqual(_, n) => number.is_some_and(|v| v <= *n),
+        }
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Constraint::*;
+
+        match self {
+            Equals(l, v) => write!(f, "{}={}", l, v),
+            NotEquals(l, v) => write!(f, "{}!={}", l, v),
+            In(l, vs) => write!(f, "{} in ({})", l, vs.join(",")),
+            NotIn(l, vs) => write!(f, "{} notin ({})", l, vs.join(",")),
+            Exists(l) => write!(f, "exists({})", l),
+            NotExists(l) => write!(f, "!exists({})", l),
+            GreaterThan(l, n) => write!(f, "{}>{}", l, n),
+            GreaterOrEqual(l, n) => write!(f, "{}>={}", l, n),
+            LessThan(l, n) => write!(f, "{}<{}", l, n),
+            LessOrEqual(l, n) => write!(f, "{}<={}", l, n),
+        }
+    }
+}
+
+impl FromStr for Constraint {
+    type Err = LatticeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid =
+            |reason: &str| LatticeError::InvalidConstraint(format!("{} in `{}`", reason, s));
+
+        if let Some(rest) = s.strip_prefix("!exists") {
+            return parenthesized(rest)
+                .and_then(label)
+                .map(Constraint::NotExists)
+                .ok_or_else(|| invalid("expected `!exists(label)`"));
+        }
+        if let Some(rest) = s
+            .strip_prefix("exists")
+            .filter(|rest| rest.trim_start().starts_with('('))
+        {
+            return parenthesized(rest)
+                .and_then(label)
+                .map(Constraint::Exists)
+                .ok_or_else(|| invalid("expected `exists(label)`"));
+        }
+        for (keyword, negated) in &[(" notin ", true), (" in ", false)] {
+            let pos = match s.find(keyword) {
+                Some(pos) => pos,
+                None => continue,
+            };
+            let label = match label(&s[..pos]) {
+                Some(label) => label,
+                None => continue,
+            };
+            let list = parenthesized(&s[pos + keyword.len()..])
+                .ok_or_else(|| invalid("expected a parenthesized list of values"))?;
+            if list.is_empty() {
+                return Err(invalid("expected at least one value"));
+            }
+            let values = list
+                .split(',')
+                .map(|v| value(v).ok_or_else(|| invalid("expected a value")))
+                .collect::<Result<Vec<_>, _>>()?;
+            return Ok(if *negated {
+                Constraint::NotIn(label, values)
+            } else {
+                Constraint::In(label, values)
+            });
+        }
+
+        let pos = s
+            .find(['=', '!', '<', '>'])
+            .ok_or_else(|| invalid("missing operator"))?;
+        let label = label(&s[..pos]).ok_or_else(|| invalid("missing label"))?;
+        let rest = &s[pos..];
+        let (op, value) = ["!=", ">=", "<=", "==", "=", ">", "<"]
+            .iter()
+            .find_map(|op| rest.strip_prefix(op).map(|v| (*op, v)))
+            .ok_or_else(|| invalid("unknown operator"))?;
+        let value = self::value(value).ok_or_else(|| invalid("expected a value"))?;
+        let number = || {
+            value
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .ok_or_else(|| invalid("expected a numeric value"))
+        };
+        Ok(match op {
+            "!=" => Constraint::NotEquals(label, value),
+            "=" | "==" => Constraint::Equals(label, value),
+            ">" => Constraint::GreaterThan(label, number()?),
+            ">=" => Constraint::GreaterOrEqual(label, number()?),
+            "<" => Constraint::LessThan(label, number()?),
+            _ => Constraint::LessOrEqual(label, number()?),
+        })
+    }
+}
+
+/// Controls how a [ConstraintSet](struct.ConstraintSet.html) is sent to hosts in an auction request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstraintMode {
+    /// Only the equality constraints are sent, in the label/value form understood by all hosts.
+    /// The remaining constraints are evaluated by the client against the labels of bidding hosts
+    Legacy,
+    /// The equality constraints are sent in their legacy form, and every constraint is also sent
+    /// as an expression for hosts that understand them. Hosts that do not will ignore the
+    /// expressions, so the client still evaluates the non-equality constraints itself
+    Extended,
+}
+
+/// A set of placement constraints, all of which must be satisfied by a host for it to be considered
+/// for an auction
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstraintSet {
+    constraints: Vec<Constraint>,
+    mode: ConstraintMode,
+}
+
+impl Default for ConstraintSet {
+    fn default() -> Self {
+        ConstraintSet {
+            constraints: vec![],
+            mode: ConstraintMode::Legacy,
+        }
+    }
+}
+
+impl ConstraintSet {
+    /// Creates a new, empty set of constraints that is sent to hosts in legacy mode
+    pub fn new() -> ConstraintSet {
+        ConstraintSet::default()
+    }
+
+    /// Adds a constraint to the set
+    pub fn constraint(mut self, constraint: Constraint) -> ConstraintSet {
+        self.constraints.push(constraint);
+        self
+    }
+
+    /// Sets how the constraints are sent to hosts in auction requests
+    pub fn mode(mut self, mode: ConstraintMode) -> ConstraintSet {
+        self.mode = mode;
+        self
+    }
+
+    /// Parses each of the given constraint expressions and adds them to the set
+    pub fn parse<S: AsRef<str>>(mut self, exprs: &[S]) -> Result<ConstraintSet, LatticeError> {
+        for expr in exprs {
+            self.constraints.push(expr.as_ref().parse()?);
+        }
+        Ok(self)
+    }
+
+    /// Returns the constraints in the set
+    pub fn constraints(&self) -> &[Constraint] {
+        &self.constraints
+    }
+
+    /// Indicates whether the set contains any constraints that hosts in legacy mode cannot evaluate,
+    /// and must therefore be evaluated by the client
+    pub fn requires_client_evaluation(&self) -> bool {
+        self.constraints.iter().any(|c| !c.is_equality())
+    }
+
+    /// Indicates whether a host with the given labels satisfies every constraint in the set
+    pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        self.constraints.iter().all(|c| c.matches(labels))
+    }
+
+    /// Returns the hosts whose labels satisfy every constraint in the set
+    pub(crate) fn matching_hosts(&self, hosts: Vec<HostProfile>) -> Vec<HostProfile> {
+        hosts
+            .into_iter()
+            .filter(|h| self.matches(&h.labels))
+            .collect()
+    }
+
+    /// Returns the constraints in the set that a host with the given labels does not satisfy
+    pub fn violations(&self, labels: &HashMap<String, String>) -> Vec<&Constraint> {
+        self.constraints
+            .iter()
+            .filter(|c| !c.matches(labels))
+            .collect()
+    }
+
+    /// Returns the equality constraints as label/value pairs, the form understood by all hosts. Fails
+    /// with `LatticeError::InvalidConstraint` if the set requires two different values for the same label,
+    /// since that cannot be expressed as label/value pairs (and no host could satisfy it)
+    pub fn equality_subset(&self) -> Result<HashMap<String, String>, LatticeError> {
+        let mut subset = HashMap::new();
+        for c in &self.constraints {
+            if let Constraint::Equals(l, v) = c {
+                match subset.insert(l.to_string(), v.to_string()) {
+                    Some(previous) if previous != *v => {
+                        return Err(LatticeError::InvalidConstraint(format!(
+                            "conflicting values `{}` and `{}` for label `{}`",
+                            previous, v, l
+                        )));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(subset)
+    }
+
+    /// Returns the expressions sent to hosts alongside the equality subset, which are empty in legacy mode
+    pub fn expressions(&self) -> Vec<String> {
+        match self.mode {
+            ConstraintMode::Legacy => vec![],
+            ConstraintMode::Extended => self.constraints.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+}
+
+impl FromIterator<Constraint> for ConstraintSet {
+    fn from_iter<I: IntoIterator<Item = Constraint>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(ConstraintSet::new(), |set, c| set.constraint(c))
+    }
+}
+
+impl From<HashMap<String, String>> for ConstraintSet {
+    fn from(constraints: HashMap<String, String>) -> Self {
+        constraints
+            .into_iter()
+            .map(|(l, v)| Constraint::Equals(l, v))
+            .collect()
+    }
+}
+
+fn label(s: &str) -> Option<String> {
+    let s = s.trim();
+    if s.is_empty() || s.contains(|c: char| c.is_whitespace() || "=!<>()".contains(c)) {
+        None
+    } else {
+        Some(s.to_string())
+    }
+}
+
+fn value(s: &str) -> Option<String> {
+    let s = s.trim();
+    if s.is_empty() || s.starts_with(|c: char| "=!<>".contains(c)) {
+        None
+    } else {
+        Some(s.to_string())
+    }
+}
+
+fn parenthesized(s: &str) -> Option<&str> {
+    s.trim()
+        .strip_prefix('(')
+        .and_then(|s| s.strip_suffix(')'))
+        .map(str::trim)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(l, v)| (l.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn parse(s: &str) -> Constraint {
+        s.parse().unwrap()
+    }
+
+    fn rejected(s: &str) -> bool {
+        matches!(
+            s.parse::<Constraint>(),
+            Err(LatticeError::InvalidConstraint(_))
+        )
+    }
+
+    #[test]
+    fn every_operator_parses() {
+        let s = |v: &str| v.to_string();
+        assert_eq!(parse("zone=east"), Constraint::Equals(s("zone"), s("east")));
+        assert_eq!(
+            parse("zone == east"),
+            Constraint::Equals(s("zone"), s("east"))
+        );
+        assert_eq!(
+            parse("zone!=east"),
+            Constraint::NotEquals(s("zone"), s("east"))
+        );
+        assert_eq!(
+            parse("zone in (east, west)"),
+            Constraint::In(s("zone"), vec![s("east"), s("west")])
+        );
+        assert_eq!(
+            parse("zone notin (east)"),
+            Constraint::NotIn(s("zone"), vec![s("east")])
+        );
+        assert_eq!(parse("exists(gpu)"), Constraint::Exists(s("gpu")));
+        assert_eq!(parse("!exists( gpu )"), Constraint::NotExists(s("gpu")));
+        assert_eq!(parse("cores>4"), Constraint::GreaterThan(s("cores"), 4.0));
+        assert_eq!(
+            parse("cores>=4"),
+            Constraint::GreaterOrEqual(s("cores"), 4.0)
+        );
+        assert_eq!(parse("cores<4.5"), Constraint::LessThan(s("cores"), 4.5));
+        assert_eq!(
+            parse("cores<=-1"),
+            Constraint::LessOrEqual(s("cores"), -1.0)
+        );
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for expr in &[
+            "zone=east",
+            "zone!=east",
+            "zone in (east,west)",
+            "zone notin (east,west)",
+            "exists(gpu)",
+            "!exists(gpu)",
+            "cores>4",
+            "cores>=4",
+            "cores<4.5",
+            "cores<=4",
+        ] {
+            assert_eq!(parse(expr).to_string(), *expr);
+        }
+    }
+
+    #[test]
+    fn malformed_constraints_are_rejected() {
+        for expr in &[
+            "",
+            "zone",
+            "=east",
+            "zone=",
+            "zone==",
+            "zone!=",
+            "zone=>5",
+            "zone=<5",
+            "zone!==east",
+            "zone=!east",
+            "zone in ()",
+            "zone in (east,)",
+            "zone notin (,east)",
+            "zone in east",
+            "exists()",
+            "!exists(gpu",
+            "cores>four",
+            "cores>NaN",
+            "cores<=inf",
+            "cores>",
+        ] {
+            assert!(rejected(expr), "`{}` should be rejected", expr);
+        }
+    }
+
+    #[test]
+    fn constraints_match_host_labels() {
+        let host = labels(&[("zone", "east"), ("cores", "8")]);
+
+        assert!(parse("zone=east").matches(&host));
+        assert!(!parse("zone!=east").matches(&host));
+        assert!(parse("zone in (west,east)").matches(&host));
+        assert!(!parse("zone notin (east)").matches(&host));
+        assert!(parse("exists(cores)").matches(&host));
+        assert!(parse("!exists(gpu)").matches(&host));
+        assert!(parse("cores>4").matches(&host));
+        assert!(parse("cores>=8").matches(&host));
+        assert!(!parse("cores<8").matches(&host));
+        assert!(parse("cores<=8").matches(&host));
+    }
+
+    #[test]
+    fn negative_constraints_match_missing_labels() {
+        let host = labels(&[]);
+
+        assert!(parse("zone!=east").matches(&host));
+        assert!(parse("zone notin (east)").matches(&host));
+        assert!(!parse("zone=east").matches(&host));
+        assert!(!parse("zone in (east)").matches(&host));
+        assert!(!parse("cores>4").matches(&host));
+    }
+
+    #[test]
+    fn numeric_constraints_ignore_non_numeric_labels() {
+        let host = labels(&[("cores", "many")]);
+
+        assert!(!parse("cores>4").matches(&host));
+        assert!(!parse("cores<4").matches(&host));
+    }
+
+    #[test]
+    fn legacy_mode_sends_no_expressions() {
+        let set = ConstraintSet::new()
+            .parse(&["zone=east", "cores>4"])
+            .unwrap();
+
+        assert!(set.expressions().is_empty());
+        assert!(set.requires_client_evaluation());
+    }
+
+    #[test]
+    fn extended_mode_sends_every_constraint() {
+        let set = ConstraintSet::new()
+            .mode(ConstraintMode::Extended)
+            .parse(&["zone=east", "cores>4"])
+            .unwrap();
+
+        assert_eq!(set.expressions(), vec!["zone=east", "cores>4"]);
+    }
+
+    #[test]
+    fn equality_only_sets_need_no_client_evaluation() {
+        let set = ConstraintSet::new().parse(&["zone=east"]).unwrap();
+
+        assert!(!set.requires_client_evaluation());
+    }
+
+    #[test]
+    fn equality_subset_holds_the_equality_constraints() {
+        let set = ConstraintSet::new()
+            .parse(&["zone=east", "zone=east", "cores>4", "tier!=db"])
+            .unwrap();
+
+        assert_eq!(set.equality_subset().unwrap(), labels(&[("zone", "east")]));
+    }
+
+    #[test]
+    fn conflicting_equality_constraints_are_rejected() {
+        let set = ConstraintSet::new()
+            .parse(&["zone=east", "zone=west"])
+            .unwrap();
+
+        assert!(matches!(
+            set.equality_subset(),
+            Err(LatticeError::InvalidConstraint(_))
+        ));
+    }
+
+    #[test]
+    fn violations_list_unsatisfied_constraints() {
+        let set = ConstraintSet::new()
+            .parse(&["zone=east", "cores>16", "exists(gpu)"])
+            .unwrap();
+        let host = labels(&[("zone", "east"), ("cores", "8")]);
+
+        let violations: Vec<String> = set
+            .violations(&host)
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        assert_eq!(violations, vec!["cores>16", "exists(gpu)"]);
+        assert!(!set.matches(&host));
+    }
+}
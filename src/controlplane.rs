@@ -17,6 +17,10 @@ pub struct ProviderAuctionRequest {
     pub provider_ref: String,
     pub binding_name: String,
     pub constraints: HashMap<String, String>,
+    /// Constraint expressions beyond simple equality, which are omitted when empty so that
+    /// hosts that do not understand them continue to receive the legacy request form
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expressions: Vec<String>,
 }
 
 impl ProviderAuctionRequest {
@@ -31,6 +35,7 @@ impl ProviderAuctionRequest {
             provider_ref: provider_ref.to_string(),
             binding_name: binding_name.to_string(),
             constraints,
+            expressions: vec![],
         }
     }
 }
@@ -49,13 +54,19 @@ pub struct ProviderAuctionResponse {
 pub struct LaunchAuctionRequest {
     pub actor_id: String,
     pub constraints: HashMap<String, String>,
+    /// Constraint expressions beyond simple equality, which are omitted when empty so that
+    /// hosts that do not understand them continue to receive the legacy request form
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expressions: Vec<String>,
 }
 
 impl LaunchAuctionRequest {
+    /// Creates a new auction request for the actor with the given legacy (equality) constraints
     pub fn new(actor: &str, constraints: HashMap<String, String>) -> LaunchAuctionRequest {
         LaunchAuctionRequest {
             actor_id: actor.to_string(),
             constraints,
+            expressions: vec![],
         }
     }
 }
@@ -95,6 +106,20 @@ pub struct ProviderLaunchAck {
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct TerminateProviderCommand {
     pub provider_ref: String,
+    /// The binding name of the provider instance, which defaults to an empty string when omitted
+    /// by older clients
+    #[serde(default)]
+    pub binding_name: String,
+}
+
+impl TerminateProviderCommand {
+    /// Creates a new command to terminate the instance of the provider with the given binding name
+    pub fn new(provider_ref: &str, binding_name: &str) -> TerminateProviderCommand {
+        TerminateProviderCommand {
+            provider_ref: provider_ref.to_string(),
+            binding_name: binding_name.to_string(),
+        }
+    }
 }
 
 /// The response submitted by a host that confirms that it has sufficient resources
@@ -104,23 +129,48 @@ pub struct LaunchAuctionResponse {
     pub host_id: String,
 }
 
-impl Client {
-    pub(crate) fn gen_launch_actor_subject(&self, host: &str) -> String {
+/// Generates the (optionally namespaced) subjects used by the lattice protocol. Shared by
+/// both the blocking and asynchronous clients
+pub(crate) trait LatticeSubjects {
+    fn namespace(&self) -> Option<&str>;
+
+    fn gen_subject(&self, subject: &str) -> String {
+        match self.namespace() {
+            Some(s) => format!("{}.wasmbus.{}", s, subject),
+            None => format!("wasmbus.{}", subject),
+        }
+    }
+
+    fn gen_launch_actor_subject(&self, host: &str) -> String {
         self.gen_subject(&format!("{}.{}.{}", CPLANE_PREFIX, host, LAUNCH_ACTOR))
         // e.g. wasmbus.control.Nxxxx.actor.launch
     }
-    pub(crate) fn gen_terminate_actor_subject(&self, host: &str) -> String {
+    fn gen_terminate_actor_subject(&self, host: &str) -> String {
         self.gen_subject(&format!("{}.{}.{}", CPLANE_PREFIX, host, TERMINATE_ACTOR))
         // e.g. wasmbus.control.Nxxxx.actor.terminate
     }
 
-    pub(crate) fn gen_launch_provider_subject(&self, host: &str) -> String {
+    fn gen_launch_provider_subject(&self, host: &str) -> String {
         self.gen_subject(&format!("{}.{}.{}", CPLANE_PREFIX, host, LAUNCH_PROVIDER))
     }
-    pub(crate) fn gen_terminate_provider_subject(&self, host: &str) -> String {
+    fn gen_terminate_provider_subject(&self, host: &str) -> String {
         self.gen_subject(&format!(
             "{}.{}.{}",
             CPLANE_PREFIX, host, TERMINATE_PROVIDER
         ))
     }
+
+    fn gen_auction_subject(&self) -> String {
+        self.gen_subject(&format!("{}.{}", CPLANE_PREFIX, AUCTION_REQ))
+    }
+
+    fn gen_provider_auction_subject(&self) -> String {
+        self.gen_subject(&format!("{}.{}", CPLANE_PREFIX, PROVIDER_AUCTION_REQ))
+    }
+}
+
+impl LatticeSubjects for Client {
+    fn namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
 }
@@ -0,0 +1,135 @@
+use std::{fmt, io};
+
+/// A convenience alias for results returned by lattice client operations
+pub type Result<T> = std::result::Result<T, LatticeError>;
+
+/// Represents the failures that can occur while interacting with a lattice. Callers can match
+/// on the variant to decide whether an operation is worth retrying
+#[derive(Debug)]
+pub enum LatticeError {
+    /// A connection to the NATS message broker could not be established
+    Connection(io::Error),
+    /// Communication with the NATS message broker failed
+    Transport(io::Error),
+    /// A lattice protocol message could not be serialized or deserialized
+    Serialization(serde_json::Error),
+    /// A request to the lattice did not receive a reply within the allotted time
+    Timeout,
+    /// An auction closed without receiving a single bid from a suitable host
+    EmptyAuction,
+    /// A host acknowledged a request with data that does not match the request
+    UnexpectedAck(String),
+    /// An actor launch was acknowledged, but the actor did not start successfully
+    LaunchFailed(String),
+    /// A lattice event or its envelope is malformed or inconsistent
+    InvalidEvent(String),
+    /// A placement constraint expression could not be parsed
+    InvalidConstraint(String),
+}
+
+impl LatticeError {
+    /// Indicates whether the failure is transient, meaning the same operation may succeed
+    /// if it is attempted again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            LatticeError::Connection(_)
+            | LatticeError::Transport(_)
+            | LatticeError::Timeout
+            | LatticeError::EmptyAuction => true,
+            LatticeError::Serialization(_)
+            | LatticeError::UnexpectedAck(_)
+            | LatticeError::LaunchFailed(_)
+            | LatticeError::InvalidEvent(_)
+            | LatticeError::InvalidConstraint(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for LatticeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LatticeError::Connection(e) => write!(f, "Failed to connect to the lattice: {}", e),
+            LatticeError::Transport(e) => write!(f, "NATS transport failure: {}", e),
+            LatticeError::Serialization(e) => write!(f, "Serialization failure: {}", e),
+            LatticeError::Timeout => write!(f, "Timed out waiting for a reply from the lattice"),
+            LatticeError::EmptyAuction => write!(f, "No hosts responded to the auction"),
+            LatticeError::UnexpectedAck(s) => {
+                write!(f, "Received unexpected acknowledgement: {}", s)
+            }
+            LatticeError::LaunchFailed(s) => write!(f, "Launch failed: {}", s),
+            LatticeError::InvalidEvent(s) => write!(f, "Invalid event: {}", s),
+            LatticeError::InvalidConstraint(s) => write!(f, "Invalid constraint: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for LatticeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LatticeError::Connection(e) | LatticeError::Transport(e) => Some(e),
+            LatticeError::Serialization(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LatticeError {
+    fn from(e: io::Error) -> LatticeError {
+        if e.kind() == io::ErrorKind::TimedOut {
+            LatticeError::Timeout
+        } else {
+            LatticeError::Transport(e)
+        }
+    }
+}
+
+impl From<serde_json::Error> for LatticeError {
+    fn from(e: serde_json::Error) -> LatticeError {
+        LatticeError::Serialization(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transient_failures_are_retryable() {
+        let io = || io::Error::new(io::ErrorKind::ConnectionReset, "reset");
+        assert!(LatticeError::Connection(io()).is_retryable());
+        assert!(LatticeError::Transport(io()).is_retryable());
+        assert!(LatticeError::Timeout.is_retryable());
+        assert!(LatticeError::EmptyAuction.is_retryable());
+    }
+
+    #[test]
+    fn permanent_failures_are_not_retryable() {
+        let json = serde_json::from_str::<u32>("nope").unwrap_err();
+        assert!(!LatticeError::Serialization(json).is_retryable());
+        assert!(!LatticeError::UnexpectedAck("ack".to_string()).is_retryable());
+        assert!(!LatticeError::LaunchFailed("actor".to_string()).is_retryable());
+        assert!(!LatticeError::InvalidEvent("event".to_string()).is_retryable());
+        assert!(!LatticeError::InvalidConstraint("zone=".to_string()).is_retryable());
+    }
+
+    #[test]
+    fn io_timeouts_map_to_timeout() {
+        let e: LatticeError = io::Error::new(io::ErrorKind::TimedOut, "timed out").into();
+        assert!(matches!(e, LatticeError::Timeout));
+    }
+
+    #[test]
+    fn other_io_errors_map_to_transport() {
+        let e: LatticeError = io::Error::new(io::ErrorKind::BrokenPipe, "broken").into();
+        match e {
+            LatticeError::Transport(e) => assert_eq!(e.kind(), io::ErrorKind::BrokenPipe),
+            other => panic!("expected a transport error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn serde_errors_map_to_serialization() {
+        let e: LatticeError = serde_json::from_str::<u32>("nope").unwrap_err().into();
+        assert!(matches!(e, LatticeError::Serialization(_)));
+    }
+}
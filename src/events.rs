@@ -1,11 +1,16 @@
-use std::fmt;
+use std::{collections::HashMap, convert::TryFrom, fmt, str::FromStr};
 
 use chrono::prelude::*;
+use serde::de::DeserializeOwned;
 use uuid::Uuid;
 
+use crate::LatticeError;
+
 /// Represents an event that may occur on a bus of connected hosts. Timestamps, identifiers, and
-/// other metadata will be provided by a [CloudEvent](struct.CloudEvent.html) envelope.
+/// other metadata will be provided by a [CloudEvent](struct.CloudEvent.html) envelope. New event types may be
+/// added in future versions, so matches against this enum must include a wildcard arm.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum BusEvent {
     /// A host process has fully started and is ready for work
     HostStarted(String),
@@ -56,30 +61,129 @@ pub enum BusEvent {
     ActorBecameHealthy { actor: String, host: String },
     /// A previously healthy actor became unhealthy. This is a higher-order event, not generated from inside wascc host
     ActorBecameUnhealthy { actor: String, host: String },
+    /// An event of a type this version of the client does not recognize, typically published by a newer host. The
+    /// event type is taken from the CloudEvent envelope and the raw data is preserved as it was received
+    Unknown { event_type: String, raw: String },
 }
 
 const EVENT_TYPE_PREFIX: &str = "wasmbus.events";
+const CLOUD_EVENTS_SPEC_VERSION: &str = "1.0";
+const DEFAULT_SOURCE: &str = "https://wascc.dev/lattice/events";
+const DEFAULT_TYPE_VERSION: &str = "0.1";
+
+/// Identifies the type of a [BusEvent](enum.BusEvent.html) without its data. The string form of a
+/// kind is the event type carried in the `type` attribute of a CloudEvent envelope, e.g.
+/// `wasmbus.events.actor_started`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum BusEventKind {
+    HostStarted,
+    HostStopped,
+    ActorStarting,
+    ActorStarted,
+    ActorStopped,
+    ActorUpdating,
+    ActorUpdateComplete,
+    ProviderLoaded,
+    ProviderRemoved,
+    ActorBindingCreated,
+    ActorBindingRemoved,
+    ActorBecameHealthy,
+    ActorBecameUnhealthy,
+}
+
+impl BusEventKind {
+    /// All of the event kinds known to this version of the client
+    pub const ALL: &'static [BusEventKind] = &[
+        BusEventKind::HostStarted,
+        BusEventKind::HostStopped,
+        BusEventKind::ActorStarting,
+        BusEventKind::ActorStarted,
+        BusEventKind::ActorStopped,
+        BusEventKind::ActorUpdating,
+        BusEventKind::ActorUpdateComplete,
+        BusEventKind::ProviderLoaded,
+        BusEventKind::ProviderRemoved,
+        BusEventKind::ActorBindingCreated,
+        BusEventKind::ActorBindingRemoved,
+        BusEventKind::ActorBecameHealthy,
+        BusEventKind::ActorBecameUnhealthy,
+    ];
+
+    /// The unqualified name of the event type, e.g. `actor_started`
+    pub fn suffix(&self) -> &'static str {
+        use BusEventKind::*;
+
+        match self {
+            HostStarted => "host_started",
+            HostStopped => "host_stopped",
+            ActorStarting => "actor_starting",
+            ActorStarted => "actor_started",
+            ActorStopped => "actor_stopped",
+            ActorUpdating => "actor_updating",
+            ActorUpdateComplete => "actor_update_complete",
+            ProviderLoaded => "provider_loaded",
+            ProviderRemoved => "provider_removed",
+            ActorBindingCreated => "actor_binding_created",
+            ActorBindingRemoved => "actor_binding_removed",
+            ActorBecameHealthy => "actor_became_healthy",
+            ActorBecameUnhealthy => "actor_became_unhealthy",
+        }
+    }
+}
+
+impl fmt::Display for BusEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", EVENT_TYPE_PREFIX, self.suffix())
+    }
+}
+
+impl FromStr for BusEventKind {
+    type Err = LatticeError;
+
+    /// Parses either a fully qualified event type (e.g. `wasmbus.events.actor_started`) or its
+    /// unqualified name (e.g. `actor_started`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let suffix = s
+            .strip_prefix(EVENT_TYPE_PREFIX)
+            .and_then(|rest| rest.strip_prefix('.'))
+            .unwrap_or(s);
+        BusEventKind::ALL
+            .iter()
+            .find(|k| k.suffix() == suffix)
+            .copied()
+            .ok_or_else(|| LatticeError::InvalidEvent(format!("Unknown event type {}", s)))
+    }
+}
 
 impl BusEvent {
-    pub fn event_type(&self) -> String {
+    /// The kind of this event, or `None` if the event type is not recognized by this client
+    pub fn kind(&self) -> Option<BusEventKind> {
         use BusEvent::*;
 
-        let suffix = match self {
-            HostStarted(_) => "host_started",
-            HostStopped(_) => "host_stopped",
-            ActorStarting { .. } => "actor_starting",
-            ActorStarted { .. } => "actor_started",
-            ActorStopped { .. } => "actor_stopped",
-            ActorUpdating { .. } => "actor_updating",
-            ActorUpdateComplete { .. } => "actor_update_complete",
-            ProviderLoaded { .. } => "provider_loaded",
-            ProviderRemoved { .. } => "provider_removed",
-            ActorBindingCreated { .. } => "actor_binding_created",
-            ActorBindingRemoved { .. } => "actor_binding_removed",
-            ActorBecameHealthy { .. } => "actor_became_healthy",
-            ActorBecameUnhealthy { .. } => "actor_became_unhealthy",
-        };
-        format!("{}.{}", EVENT_TYPE_PREFIX, suffix)
+        Some(match self {
+            HostStarted(_) => BusEventKind::HostStarted,
+            HostStopped(_) => BusEventKind::HostStopped,
+            ActorStarting { .. } => BusEventKind::ActorStarting,
+            ActorStarted { .. } => BusEventKind::ActorStarted,
+            ActorStopped { .. } => BusEventKind::ActorStopped,
+            ActorUpdating { .. } => BusEventKind::ActorUpdating,
+            ActorUpdateComplete { .. } => BusEventKind::ActorUpdateComplete,
+            ProviderLoaded { .. } => BusEventKind::ProviderLoaded,
+            ProviderRemoved { .. } => BusEventKind::ProviderRemoved,
+            ActorBindingCreated { .. } => BusEventKind::ActorBindingCreated,
+            ActorBindingRemoved { .. } => BusEventKind::ActorBindingRemoved,
+            ActorBecameHealthy { .. } => BusEventKind::ActorBecameHealthy,
+            ActorBecameUnhealthy { .. } => BusEventKind::ActorBecameUnhealthy,
+            Unknown { .. } => return None,
+        })
+    }
+
+    pub fn event_type(&self) -> String {
+        match self {
+            BusEvent::Unknown { event_type, .. } => event_type.to_string(),
+            _ => self.kind().map(|k| k.to_string()).unwrap_or_default(),
+        }
     }
 
     pub fn subject(&self) -> String {
@@ -118,10 +222,155 @@ impl BusEvent {
             ActorBecameHealthy { actor, .. } | ActorBecameUnhealthy { actor, .. } => {
                 actor.to_string()
             }
+            Unknown { .. } => String::new(),
         }
     }
 }
 
+impl BusEvent {
+    /// The host on which the event occurred, if known
+    pub fn host(&self) -> Option<&str> {
+        use BusEvent::*;
+
+        match self {
+            HostStarted(host) | HostStopped(host) => Some(host),
+            ActorStarting { host, .. }
+            | ActorStarted { host, .. }
+            | ActorStopped { host, .. }
+            | ActorUpdating { host, .. }
+            | ActorUpdateComplete { host, .. }
+            | ProviderLoaded { host, .. }
+            | ProviderRemoved { host, .. }
+            | ActorBindingCreated { host, .. }
+            | ActorBindingRemoved { host, .. }
+            | ActorBecameHealthy { host, .. }
+            | ActorBecameUnhealthy { host, .. } => Some(host),
+            Unknown { .. } => None,
+        }
+    }
+
+    /// The actor to which the event pertains, if any
+    pub fn actor(&self) -> Option<&str> {
+        use BusEvent::*;
+
+        match self {
+            ActorStarting { actor, .. }
+            | ActorStarted { actor, .. }
+            | ActorStopped { actor, .. }
+            | ActorUpdating { actor, .. }
+            | ActorUpdateComplete { actor, .. }
+            | ActorBindingCreated { actor, .. }
+            | ActorBindingRemoved { actor, .. }
+            | ActorBecameHealthy { actor, .. }
+            | ActorBecameUnhealthy { actor, .. } => Some(actor),
+            _ => None,
+        }
+    }
+
+    /// The capability ID to which the event pertains, if any
+    pub fn capid(&self) -> Option<&str> {
+        use BusEvent::*;
+
+        match self {
+            ProviderLoaded { capid, .. }
+            | ProviderRemoved { capid, .. }
+            | ActorBindingCreated { capid, .. }
+            | ActorBindingRemoved { capid, .. } => Some(capid),
+            _ => None,
+        }
+    }
+
+    /// The capability provider instance (binding) name to which the event pertains, if any
+    pub fn instance_name(&self) -> Option<&str> {
+        use BusEvent::*;
+
+        match self {
+            ProviderLoaded { instance_name, .. }
+            | ProviderRemoved { instance_name, .. }
+            | ActorBindingCreated { instance_name, .. }
+            | ActorBindingRemoved { instance_name, .. } => Some(instance_name),
+            _ => None,
+        }
+    }
+}
+
+/// Selects the bus events of interest to a subscriber. Each criterion accepts any of the values
+/// supplied for it, and an event must satisfy every criterion that has at least one value. An
+/// empty filter matches all events. Filters are evaluated client-side against events that have
+/// already been received
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventFilter {
+    event_types: Vec<String>,
+    hosts: Vec<String>,
+    actors: Vec<String>,
+    capids: Vec<String>,
+    instance_names: Vec<String>,
+}
+
+impl EventFilter {
+    /// Creates a new filter that matches all events
+    pub fn new() -> EventFilter {
+        EventFilter::default()
+    }
+
+    /// Matches events of the given type, either as a suffix (e.g. `actor_started`) or as a
+    /// fully qualified type (e.g. `wasmbus.events.actor_started`)
+    pub fn event_type(mut self, event_type: &str) -> EventFilter {
+        let suffix = event_type
+            .trim_start_matches(EVENT_TYPE_PREFIX)
+            .trim_start_matches('.');
+        self.event_types.push(suffix.to_string());
+        self
+    }
+
+    /// Matches events of the given kind
+    pub fn kind(mut self, kind: BusEventKind) -> EventFilter {
+        self.event_types.push(kind.suffix().to_string());
+        self
+    }
+
+    /// Matches events that occurred on the given host
+    pub fn host(mut self, host: &str) -> EventFilter {
+        self.hosts.push(host.to_string());
+        self
+    }
+
+    /// Matches events pertaining to the given actor
+    pub fn actor(mut self, actor: &str) -> EventFilter {
+        self.actors.push(actor.to_string());
+        self
+    }
+
+    /// Matches events pertaining to the given capability ID
+    pub fn capid(mut self, capid: &str) -> EventFilter {
+        self.capids.push(capid.to_string());
+        self
+    }
+
+    /// Matches events pertaining to the given capability provider instance (binding) name
+    pub fn instance_name(mut self, instance_name: &str) -> EventFilter {
+        self.instance_names.push(instance_name.to_string());
+        self
+    }
+
+    /// Indicates whether the given event satisfies the filter
+    pub fn matches(&self, event: &BusEvent) -> bool {
+        let event_type = event.event_type();
+        let suffix = event_type
+            .trim_start_matches(EVENT_TYPE_PREFIX)
+            .trim_start_matches('.');
+        accepts(&self.event_types, Some(suffix))
+            && accepts(&self.hosts, event.host())
+            && accepts(&self.actors, event.actor())
+            && accepts(&self.capids, event.capid())
+            && accepts(&self.instance_names, event.instance_name())
+    }
+}
+
+fn accepts(allowed: &[String], value: Option<&str>) -> bool {
+    allowed.is_empty() || value.is_some_and(|v| allowed.iter().any(|a| a == v))
+}
+
 impl fmt::Display for BusEvent {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use BusEvent::*;
@@ -179,12 +428,24 @@ impl fmt::Display for BusEvent {
             ActorBecameUnhealthy { host, actor } => {
                 write!(f, "[{}] Actor {} became unhealthy", host, actor)
             }
+            Unknown { event_type, .. } => write!(f, "[?] Unrecognized event {}", event_type),
         }
     }
 }
 
+/// Determines how the payload of a [CloudEvent](struct.CloudEvent.html) is represented in its `data` attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    /// The payload is serialized to a JSON string, which is then embedded as a string value. This is the
+    /// form emitted by waSCC hosts and understood by all versions of this client
+    Legacy,
+    /// The payload is embedded directly as a JSON value, as described by the CloudEvents JSON format
+    Structured,
+}
+
 /// An envelope for an event that corresponds to the [CloudEvents 1.0 Spec](https://github.com/cloudevents/spec/blob/v1.0/spec.md)
-/// with the intent to be serialized in the JSON format.
+/// with the intent to be serialized in the JSON format. The `data` attribute may hold either a structured
+/// JSON payload or the legacy string-encoded form, use [payload](#method.payload) to decode either.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct CloudEvent {
     #[serde(rename = "specversion")]
@@ -201,25 +462,517 @@ pub struct CloudEvent {
     pub event_time: DateTime<Utc>,
     #[serde(rename = "datacontenttype")]
     pub content_type: String,
+    #[serde(
+        rename = "dataschema",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub data_schema: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub subject: Option<String>,
-    pub data: String,
+    pub data: serde_json::Value,
+    /// Extension context attributes, serialized alongside the core attributes
+    #[serde(flatten)]
+    pub extensions: HashMap<String, serde_json::Value>,
 }
 
-impl From<BusEvent> for CloudEvent {
-    fn from(event: BusEvent) -> CloudEvent {
-        let raw_data = serde_json::to_string(&event).unwrap();
+impl CloudEvent {
+    /// Wraps a bus event in a new envelope, encoding the event data in the given format. The envelope is
+    /// not validated, since some events (such as a `BusEvent::Unknown` without a type) cannot produce a valid
+    /// one; use a [CloudEventBuilder](struct.CloudEventBuilder.html) to customize and validate the envelope
+    pub fn from_event(event: BusEvent, format: DataFormat) -> CloudEvent {
+        CloudEventBuilder::new(event).data_format(format).assemble()
+    }
+
+    /// Checks that the envelope carries the attributes required by the CloudEvents 1.0 specification: a
+    /// `specversion` of 1.0, a non-empty `id` and `type`, a `source` and `dataschema` (when present) that
+    /// are URI-references, a non-empty `subject` (when present) and extension attribute names made up of lowercase letters and digits.
+    /// The `time` attribute is always RFC3339 as it can only hold a valid timestamp. Returns
+    /// `LatticeError::InvalidEvent` describing every violation found
+    pub fn validate(&self) -> Result<(), LatticeError> {
+        let mut problems = vec![];
+        if self.cloud_events_version != CLOUD_EVENTS_SPEC_VERSION {
+            problems.push(format!(
+                "unsupported specversion {}",
+                self.cloud_events_version
+            ));
+        }
+        if self.event_id.is_empty() {
+            problems.push("id must not be empty".to_string());
+        }
+        if self.event_type.is_empty() {
+            problems.push("type must not be empty".to_string());
+        }
+        if !is_uri_reference(&self.source) {
+            problems.push(format!("source {:?} is not a URI-reference", self.source));
+        }
+        if self.content_type.is_empty() {
+            problems.push("datacontenttype must not be empty".to_string());
+        }
+        if let Some(schema) = self.data_schema.as_ref().filter(|s| !is_uri_reference(s)) {
+            problems.push(format!("dataschema {:?} is not a URI-reference", schema));
+        }
+        if self.subject.as_ref().is_some_and(|s| s.is_empty()) {
+            problems.push("subject must not be empty when present".to_string());
+        }
+        for name in self.extensions.keys() {
+            if !is_extension_name(name) {
+                problems.push(format!("invalid extension attribute name {:?}", name));
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(LatticeError::InvalidEvent(problems.join("; ")))
+        }
+    }
+
+    /// Indicates whether the event data is held in the legacy string-encoded form
+    pub fn is_legacy(&self) -> bool {
+        self.data.is_string()
+    }
+
+    /// Decodes the event data into the given type, accepting both the structured and the legacy
+    /// string-encoded forms
+    pub fn payload<T: DeserializeOwned>(&self) -> Result<T, LatticeError> {
+        match self.data {
+            serde_json::Value::String(ref s) => Ok(serde_json::from_str(s)?),
+            ref v => Ok(T::deserialize(v)?),
+        }
+    }
+
+    /// Returns the event data as raw JSON text, unwrapping the legacy string-encoded form
+    pub fn raw_data(&self) -> String {
+        match self.data {
+            serde_json::Value::String(ref s) => s.to_string(),
+            ref v => v.to_string(),
+        }
+    }
+}
+
+/// A builder used to wrap a [BusEvent](enum.BusEvent.html) in a [CloudEvent](struct.CloudEvent.html)
+/// envelope with custom attributes. Attributes that are not supplied receive the same defaults used
+/// by waSCC hosts, and the subject defaults to the one derived from the event
+pub struct CloudEventBuilder {
+    event: BusEvent,
+    source: String,
+    subject: Option<String>,
+    event_type_version: String,
+    format: DataFormat,
+    extensions: HashMap<String, serde_json::Value>,
+}
+
+impl CloudEventBuilder {
+    /// Creates a new builder for an envelope around the given event
+    pub fn new(event: BusEvent) -> CloudEventBuilder {
+        CloudEventBuilder {
+            event,
+            source: DEFAULT_SOURCE.to_string(),
+            subject: None,
+            event_type_version: DEFAULT_TYPE_VERSION.to_string(),
+            format: DataFormat::Legacy,
+            extensions: HashMap::new(),
+        }
+    }
+
+    /// Sets the URI-reference identifying the context in which the event happened
+    pub fn source(mut self, source: &str) -> CloudEventBuilder {
+        self.source = source.to_string();
+        self
+    }
+
+    /// Overrides the subject derived from the event
+    pub fn subject(mut self, subject: &str) -> CloudEventBuilder {
+        self.subject = Some(subject.to_string());
+        self
+    }
+
+    /// Sets the version of the event type's schema
+    pub fn type_version(mut self, version: &str) -> CloudEventBuilder {
+        self.event_type_version = version.to_string();
+        self
+    }
+
+    /// Sets the format used to encode the event data
+    pub fn data_format(mut self, format: DataFormat) -> CloudEventBuilder {
+        self.format = format;
+        self
+    }
+
+    /// Adds an extension context attribute. Names must consist of lowercase letters and digits
+    pub fn extension(
+        mut self,
+        name: &str,
+        value: impl Into<serde_json::Value>,
+    ) -> CloudEventBuilder {
+        self.extensions.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Produces the envelope, returning `LatticeError::InvalidEvent` if it does not satisfy
+    /// [validate](struct.CloudEvent.html#method.validate)
+    pub fn build(self) -> Result<CloudEvent, LatticeError> {
+        let ce = self.assemble();
+        ce.validate()?;
+        Ok(ce)
+    }
+
+    fn assemble(self) -> CloudEvent {
+        // Bus events consist of strings and booleans, so serializing them cannot fail
+        let data = match (self.format, &self.event) {
+            (DataFormat::Legacy, BusEvent::Unknown { raw, .. }) => {
+                serde_json::Value::String(raw.to_string())
+            }
+            (DataFormat::Structured, BusEvent::Unknown { raw, .. }) => serde_json::from_str(raw)
+                .unwrap_or_else(|_| serde_json::Value::String(raw.to_string())),
+            (DataFormat::Legacy, e) => {
+                serde_json::Value::String(serde_json::to_string(e).unwrap_or_default())
+            }
+            (DataFormat::Structured, e) => serde_json::to_value(e).unwrap_or_default(),
+        };
+        let event = self.event;
+        let subject = self.subject.unwrap_or_else(|| event.subject());
 
         CloudEvent {
-            cloud_events_version: "1.0".to_string(),
+            cloud_events_version: CLOUD_EVENTS_SPEC_VERSION.to_string(),
             event_type: event.event_type(),
-            event_type_version: "0.1".to_string(),
-            source: "https://wascc.dev/lattice/events".to_string(),
-            subject: Some(event.subject()),
+            event_type_version: self.event_type_version,
+            source: self.source,
+            subject: if subject.is_empty() {
+                None
+            } else {
+                Some(subject)
+            },
             event_id: Uuid::new_v4().to_hyphenated().to_string(),
             event_time: Utc::now(),
             content_type: "application/json".to_string(),
-            data: raw_data,
+            data_schema: None,
+            data,
+            extensions: self.extensions,
+        }
+    }
+}
+
+/// Core context attribute names, which may not be used for extension attributes
+const RESERVED_ATTRIBUTES: &[&str] = &[
+    "specversion",
+    "type",
+    "typeversion",
+    "source",
+    "id",
+    "time",
+    "datacontenttype",
+    "dataschema",
+    "subject",
+    "data",
+    "data_base64",
+];
+
+fn is_extension_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && !RESERVED_ATTRIBUTES.contains(&name)
+}
+
+/// Performs a lexical check that the value could be an RFC 3986 URI-reference: non-empty, made up of
+/// printable ASCII characters that are permitted in URIs, with well-formed percent-encodings
+fn is_uri_reference(value: &str) -> bool {
+    if value.is_empty() {
+        return false;
+    }
+    let bytes = value.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if b == b'%' {
+            if i + 2 >= bytes.len() {
+                return false;
+            }
+            if !(bytes[i + 1].is_ascii_hexdigit() && bytes[i + 2].is_ascii_hexdigit()) {
+                return false;
+            }
+            i += 3;
+            continue;
+        }
+        if !b.is_ascii_graphic() || b"\"<>\\^`{|}".contains(&b) {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+impl From<BusEvent> for CloudEvent {
+    /// Wraps a bus event in a new envelope using the legacy data format, for compatibility with
+    /// existing lattice consumers
+    fn from(event: BusEvent) -> CloudEvent {
+        CloudEvent::from_event(event, DataFormat::Legacy)
+    }
+}
+
+impl TryFrom<CloudEvent> for BusEvent {
+    type Error = LatticeError;
+
+    /// Extracts the bus event carried by a CloudEvent envelope. Envelopes with an unrecognized type produce
+    /// `BusEvent::Unknown`. Envelopes whose type does not agree with the decoded payload are rejected with
+    /// `LatticeError::InvalidEvent`. The subject is not checked, as publishers may override it
+    fn try_from(ce: CloudEvent) -> Result<BusEvent, LatticeError> {
+        let kind = match ce.event_type.parse::<BusEventKind>() {
+            Ok(kind) => kind,
+            Err(_) => {
+                return Ok(BusEvent::Unknown {
+                    raw: ce.raw_data(),
+                    event_type: ce.event_type,
+                })
+            }
+        };
+        let event: BusEvent = ce.payload()?;
+        if event.kind() != Some(kind) {
+            return Err(LatticeError::InvalidEvent(format!(
+                "Envelope type {} does not match payload type {}",
+                ce.event_type,
+                event.event_type()
+            )));
+        }
+        Ok(event)
+    }
+}
+
+/// An error produced when a message received on the lattice events subject cannot be decoded
+/// into a [BusEvent](enum.BusEvent.html). The raw payload is retained for diagnosis
+#[derive(Debug)]
+pub struct EventDecodeError {
+    /// The raw payload of the message that could not be decoded
+    pub raw: Vec<u8>,
+    /// The reason the payload could not be decoded
+    pub source: LatticeError,
+}
+
+impl EventDecodeError {
+    /// Returns the raw payload as (lossy) UTF-8 text
+    pub fn payload(&self) -> String {
+        String::from_utf8_lossy(&self.raw).to_string()
+    }
+}
+
+impl fmt::Display for EventDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to decode lattice event: {}", self.source)
+    }
+}
+
+impl std::error::Error for EventDecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Decodes a bus event from the raw payload of a message published on the lattice events subject. Events
+/// with a type this client does not recognize are decoded as `BusEvent::Unknown`
+pub(crate) fn decode_event(data: &[u8]) -> Result<BusEvent, EventDecodeError> {
+    serde_json::from_slice::<CloudEvent>(data)
+        .map_err(LatticeError::from)
+        .and_then(BusEvent::try_from)
+        .map_err(|source| EventDecodeError {
+            raw: data.to_vec(),
+            source,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn actor_started() -> BusEvent {
+        BusEvent::ActorStarted {
+            actor: "Mactor".to_string(),
+            host: "Nhost".to_string(),
+        }
+    }
+
+    fn provider_loaded() -> BusEvent {
+        BusEvent::ProviderLoaded {
+            capid: "wascc:http_server".to_string(),
+            instance_name: "default".to_string(),
+            host: "Nhost".to_string(),
+        }
+    }
+
+    fn round_trip(ce: &CloudEvent) -> Result<BusEvent, EventDecodeError> {
+        decode_event(&serde_json::to_vec(ce).unwrap())
+    }
+
+    fn envelope(event_type: &str, data: serde_json::Value) -> Vec<u8> {
+        serde_json::to_vec(&serde_json::json!({
+            "specversion": "1.0",
+            "type": event_type,
+            "typeversion": "0.1",
+            "source": "https://wascc.dev/lattice/events",
+            "id": "f2a0c4a4-6ef1-4f3c-9b3e-2d9d1c3f7f10",
+            "time": "2020-06-01T12:00:00Z",
+            "datacontenttype": "application/json",
+            "data": data,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn legacy_string_data_decodes() {
+        let data = serde_json::Value::String(serde_json::to_string(&actor_started()).unwrap());
+        let event = decode_event(&envelope("wasmbus.events.actor_started", data)).unwrap();
+        assert_eq!(event, actor_started());
+    }
+
+    #[test]
+    fn structured_data_decodes() {
+        let data = serde_json::to_value(actor_started()).unwrap();
+        let event = decode_event(&envelope("wasmbus.events.actor_started", data)).unwrap();
+        assert_eq!(event, actor_started());
+    }
+
+    #[test]
+    fn both_formats_round_trip() {
+        for format in &[DataFormat::Legacy, DataFormat::Structured] {
+            let ce = CloudEvent::from_event(provider_loaded(), *format);
+            assert_eq!(ce.is_legacy(), *format == DataFormat::Legacy);
+            assert_eq!(round_trip(&ce).unwrap(), provider_loaded());
         }
     }
+
+    #[test]
+    fn unrecognized_types_fall_back_to_unknown() {
+        let data = serde_json::json!({ "Rebalanced": { "host": "Nhost" } });
+        let event = decode_event(&envelope("wasmbus.events.rebalanced", data.clone())).unwrap();
+        match event {
+            BusEvent::Unknown { event_type, raw } => {
+                assert_eq!(event_type, "wasmbus.events.rebalanced");
+                assert_eq!(
+                    serde_json::from_str::<serde_json::Value>(&raw).unwrap(),
+                    data
+                );
+            }
+            other => panic!("expected an unknown event, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn event_kinds_round_trip_through_type_strings() {
+        for kind in BusEventKind::ALL {
+            assert_eq!(kind.to_string().parse::<BusEventKind>().unwrap(), *kind);
+            assert_eq!(kind.suffix().parse::<BusEventKind>().unwrap(), *kind);
+        }
+        assert!("wasmbus.events.rebalanced".parse::<BusEventKind>().is_err());
+        assert_eq!(actor_started().kind(), Some(BusEventKind::ActorStarted));
+    }
+
+    #[test]
+    fn tampered_type_is_rejected() {
+        let data = serde_json::to_value(actor_started()).unwrap();
+        let err = decode_event(&envelope("wasmbus.events.actor_stopped", data)).unwrap_err();
+        assert!(matches!(err.source, LatticeError::InvalidEvent(_)));
+        assert!(err.payload().contains("actor_stopped"));
+    }
+
+    #[test]
+    fn malformed_payload_retains_raw_data() {
+        let err = decode_event(b"not an event").unwrap_err();
+        assert!(matches!(err.source, LatticeError::Serialization(_)));
+        assert_eq!(err.payload(), "not an event");
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        let filter = EventFilter::new();
+        assert!(filter.matches(&actor_started()));
+        assert!(filter.matches(&BusEvent::HostStopped("Nhost".to_string())));
+    }
+
+    #[test]
+    fn filter_matches_event_types() {
+        assert!(EventFilter::new()
+            .event_type("actor_started")
+            .matches(&actor_started()));
+        assert!(EventFilter::new()
+            .event_type("wasmbus.events.actor_started")
+            .matches(&actor_started()));
+        assert!(EventFilter::new()
+            .kind(BusEventKind::ActorStopped)
+            .kind(BusEventKind::ActorStarted)
+            .matches(&actor_started()));
+        assert!(!EventFilter::new()
+            .event_type("actor_stopped")
+            .matches(&actor_started()));
+    }
+
+    #[test]
+    fn filter_matches_hosts_and_actors() {
+        assert!(EventFilter::new().host("Nhost").matches(&actor_started()));
+        assert!(!EventFilter::new().host("Nother").matches(&actor_started()));
+        assert!(EventFilter::new().actor("Mactor").matches(&actor_started()));
+        assert!(!EventFilter::new().actor("Mother").matches(&actor_started()));
+        assert!(!EventFilter::new()
+            .actor("Mactor")
+            .matches(&provider_loaded()));
+    }
+
+    #[test]
+    fn filter_matches_providers() {
+        assert!(EventFilter::new()
+            .capid("wascc:http_server")
+            .instance_name("default")
+            .matches(&provider_loaded()));
+        assert!(!EventFilter::new()
+            .capid("wascc:keyvalue")
+            .matches(&provider_loaded()));
+        assert!(!EventFilter::new()
+            .instance_name("other")
+            .matches(&provider_loaded()));
+        assert!(!EventFilter::new()
+            .capid("wascc:http_server")
+            .matches(&actor_started()));
+    }
+
+    #[test]
+    fn every_criterion_must_match() {
+        let filter = EventFilter::new().host("Nhost").actor("Mother");
+        assert!(!filter.matches(&actor_started()));
+    }
+
+    #[test]
+    fn builder_subject_override_round_trips() {
+        let ce = CloudEventBuilder::new(actor_started())
+            .subject("custom-subject")
+            .build()
+            .unwrap();
+        assert_eq!(ce.subject.as_deref(), Some("custom-subject"));
+        assert_eq!(round_trip(&ce).unwrap(), actor_started());
+    }
+
+    #[test]
+    fn data_schema_round_trips() {
+        let mut ce = CloudEvent::from(actor_started());
+        ce.data_schema = Some("https://wascc.dev/schemas/actor_started.json".to_string());
+        let json = serde_json::to_value(&ce).unwrap();
+        assert_eq!(
+            json["dataschema"],
+            "https://wascc.dev/schemas/actor_started.json"
+        );
+
+        let decoded: CloudEvent = serde_json::from_value(json).unwrap();
+        assert!(decoded.extensions.is_empty());
+        assert!(decoded.validate().is_ok());
+        assert_eq!(decoded, ce);
+        assert_eq!(round_trip(&decoded).unwrap(), actor_started());
+    }
+
+    #[test]
+    fn conversion_does_not_validate() {
+        let ce = CloudEvent::from(BusEvent::Unknown {
+            event_type: String::new(),
+            raw: "{}".to_string(),
+        });
+        assert!(matches!(ce.validate(), Err(LatticeError::InvalidEvent(_))));
+    }
 }
@@ -0,0 +1,435 @@
+//! Actor health monitoring. A [HealthMonitor](struct.HealthMonitor.html) periodically probes the lattice
+//! inventory and tracks actor lifecycle events, publishing `ActorBecameHealthy` and `ActorBecameUnhealthy`
+//! events onto the lattice bus whenever an actor's health changes.
+
+use std::{collections::HashMap, time::Duration};
+
+use crossbeam::channel::{tick, unbounded};
+use wascap::prelude::*;
+
+use crate::{BusEvent, Client, InventoryReport, Result};
+
+/// Configures how often a [HealthMonitor](struct.HealthMonitor.html) probes the lattice and how
+/// many consecutive probes it takes for an actor to change health status
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthMonitorConfig {
+    /// The period between inventory probes
+    pub probe_interval: Duration,
+    /// The number of consecutive probes an actor must be missing from before it becomes unhealthy
+    pub unhealthy_threshold: u32,
+    /// The number of consecutive probes an unhealthy actor must be present in before it becomes healthy
+    pub healthy_threshold: u32,
+}
+
+impl Default for HealthMonitorConfig {
+    fn default() -> Self {
+        HealthMonitorConfig {
+            probe_interval: Duration::from_secs(10),
+            unhealthy_threshold: 3,
+            healthy_threshold: 1,
+        }
+    }
+}
+
+/// The health status of a single actor instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    Healthy,
+    Unhealthy,
+}
+
+/// The health state tracked for a single actor instance running on a host
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActorHealth {
+    pub status: HealthStatus,
+    /// The number of consecutive probes the actor has been missing from
+    pub consecutive_misses: u32,
+    /// The number of consecutive probes the actor has been present in
+    pub consecutive_hits: u32,
+}
+
+impl ActorHealth {
+    fn healthy() -> ActorHealth {
+        ActorHealth {
+            status: HealthStatus::Healthy,
+            consecutive_misses: 0,
+            consecutive_hits: 0,
+        }
+    }
+}
+
+/// Tracks the health of every actor instance in the lattice from lifecycle events and inventory probes.
+/// Actors are tracked from the moment they are reported started (or first discovered by a probe) until
+/// they are reported stopped, so actors that stop in a predictable manner never become unhealthy
+#[derive(Debug, Clone, Default)]
+pub struct HealthTracker {
+    config: HealthMonitorConfig,
+    actors: HashMap<(String, String), ActorHealth>,
+}
+
+impl HealthTracker {
+    /// Creates a new tracker that changes the health status of actors using the given thresholds
+    pub fn new(config: HealthMonitorConfig) -> HealthTracker {
+        HealthTracker {
+            config,
+            actors: HashMap::new(),
+        }
+    }
+
+    /// Returns the health of the given actor on the given host, if it is being tracked
+    pub fn health(&self, host: &str, actor: &str) -> Option<&ActorHealth> {
+        self.actors.get(&(host.to_string(), actor.to_string()))
+    }
+
+    /// Returns the health of every tracked actor instance, keyed by host and actor
+    pub fn actors(&self) -> &HashMap<(String, String), ActorHealth> {
+        &self.actors
+    }
+
+    /// Updates the tracked actors from a lattice event, returning any resulting health transitions
+    pub fn apply_event(&mut self, event: &BusEvent) -> Vec<BusEvent> {
+        let mut transitions = vec![];
+        match event {
+            BusEvent::ActorStarted { actor, host } => {
+                let key = (host.to_string(), actor.to_string());
+                if let Some(ActorHealth {
+                    status: HealthStatus::Unhealthy,
+                    ..
+                }) = self.actors.get(&key)
+                {
+                    transitions.push(BusEvent::ActorBecameHealthy {
+                        actor: actor.to_string(),
+                        host: host.to_string(),
+                    });
+                }
+                self.actors.insert(key, ActorHealth::healthy());
+            }
+            BusEvent::ActorStopped { actor, host } => {
+                self.actors.remove(&(host.to_string(), actor.to_string()));
+            }
+            BusEvent::HostStopped(host) => {
+                self.actors.retain(|(h, _), _| h != host);
+            }
+            _ => {}
+        }
+        transitions
+    }
+
+    /// Updates the tracked actors from the results of an inventory probe, returning any resulting
+    /// health transitions. Actors seen for the first time are tracked as healthy. Actors on hosts whose
+    /// replies could not be decoded are left as they are, since the probe says nothing about them
+    pub fn apply_probe(
+        &mut self,
+        report: &InventoryReport<HashMap<String, Vec<Claims<Actor>>>>,
+    ) -> Vec<BusEvent> {
+        let inventory = &report.items;
+        let undecoded: Vec<&str> = report
+            .errors
+            .iter()
+            .filter_map(|e| e.host.as_deref())
+            .collect();
+        let mut transitions = vec![];
+        for (host, actors) in inventory {
+            for actor in actors {
+                self.actors
+                    .entry((host.to_string(), actor.subject.to_string()))
+                    .or_insert_with(ActorHealth::healthy);
+            }
+        }
+        for ((host, actor), health) in self.actors.iter_mut() {
+            if undecoded.contains(&host.as_str()) {
+                continue;
+            }
+            let present = inventory
+                .get(host)
+                .is_some_and(|actors| actors.iter().any(|a| a.subject == *actor));
+            if present {
+                health.consecutive_hits += 1;
+                health.consecutive_misses = 0;
+                if health.status == HealthStatus::Unhealthy
+                    && health.consecutive_hits >= self.config.healthy_threshold
+                {
+                    health.status = HealthStatus::Healthy;
+                    transitions.push(BusEvent::ActorBecameHealthy {
+                        actor: actor.to_string(),
+                        host: host.to_string(),
+                    });
+                }
+            } else {
+                health.consecutive_misses += 1;
+                health.consecutive_hits = 0;
+                if health.status == HealthStatus::Healthy
+                    && health.consecutive_misses >= self.config.unhealthy_threshold
+                {
+                    health.status = HealthStatus::Unhealthy;
+                    transitions.push(BusEvent::ActorBecameUnhealthy {
+                        actor: actor.to_string(),
+                        host: host.to_string(),
+                    });
+                }
+            }
+        }
+        transitions
+    }
+}
+
+/// Monitors the health of every actor instance in the lattice, probing the inventory with a client and
+/// publishing the transitions found by its [HealthTracker](struct.HealthTracker.html)
+pub struct HealthMonitor {
+    client: Client,
+    tracker: HealthTracker,
+}
+
+impl HealthMonitor {
+    /// Creates a new health monitor that uses the given client to probe the lattice and publish events
+    pub fn new(client: Client, config: HealthMonitorConfig) -> HealthMonitor {
+        HealthMonitor {
+            client,
+            tracker: HealthTracker::new(config),
+        }
+    }
+
+    /// Returns the health state tracked by the monitor
+    pub fn tracker(&self) -> &HealthTracker {
+        &self.tracker
+    }
+
+    /// Updates the tracked actors from a lattice event, returning any resulting health transitions
+    pub fn apply_event(&mut self, event: &BusEvent) -> Vec<BusEvent> {
+        self.tracker.apply_event(event)
+    }
+
+    /// Updates the tracked actors from the results of an inventory probe, returning any resulting
+    /// health transitions
+    pub fn apply_probe(
+        &mut self,
+        report: &InventoryReport<HashMap<String, Vec<Claims<Actor>>>>,
+    ) -> Vec<BusEvent> {
+        self.tracker.apply_probe(report)
+    }
+
+    /// Probes the lattice inventory once, publishing and returning any resulting health transitions
+    pub fn probe(&mut self) -> Result<Vec<BusEvent>> {
+        let report = self.client.get_actors()?;
+        for e in &report.errors {
+            log::warn!("{}", e);
+        }
+        let transitions = self.apply_probe(&report);
+        self.publish(&transitions)?;
+        Ok(transitions)
+    }
+
+    /// Runs the monitor until the lattice event subscription ends, probing the inventory on the configured
+    /// interval and applying lifecycle events as they arrive. Every health transition is published onto
+    /// the lattice bus and passed to the given callback. Failed probes and publications are logged and
+    /// do not stop the monitor
+    pub fn run<F>(&mut self, mut on_transition: F) -> Result<()>
+    where
+        F: FnMut(&BusEvent),
+    {
+        let (sender, receiver) = unbounded();
+        let _sub = self.client.watch_events(sender)?;
+        let ticker = tick(self.tracker.config.probe_interval);
+        self.run_probe(&mut on_transition);
+        loop {
+            crossbeam::select! {
+                recv(receiver) -> msg => match msg {
+                    Ok(Ok(event)) => {
+                        let transitions = self.apply_event(&event);
+                        if let Err(e) = self.publish(&transitions) {
+                            log::warn!("Failed to publish health transitions: {}", e);
+                        }
+                        transitions.iter().for_each(&mut on_transition);
+                    }
+                    Ok(Err(e)) => log::warn!("{}", e),
+                    Err(_) => return Ok(()),
+                },
+                recv(ticker) -> _ => self.run_probe(&mut on_transition),
+            }
+        }
+    }
+
+    fn run_probe<F>(&mut self, on_transition: &mut F)
+    where
+        F: FnMut(&BusEvent),
+    {
+        match self.probe() {
+            Ok(transitions) => transitions.iter().for_each(on_transition),
+            Err(e) => log::warn!("Health probe failed: {}", e),
+        }
+    }
+
+    fn publish(&self, transitions: &[BusEvent]) -> Result<()> {
+        for event in transitions {
+            self.client.publish_event(event.clone())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{InventoryReplyError, LatticeError};
+
+    fn tracker() -> HealthTracker {
+        HealthTracker::new(HealthMonitorConfig {
+            unhealthy_threshold: 2,
+            healthy_threshold: 2,
+            ..Default::default()
+        })
+    }
+
+    fn started(actor: &str, host: &str) -> BusEvent {
+        BusEvent::ActorStarted {
+            actor: actor.to_string(),
+            host: host.to_string(),
+        }
+    }
+
+    fn probe(actors: &[(&str, &str)]) -> InventoryReport<HashMap<String, Vec<Claims<Actor>>>> {
+        let mut items: HashMap<String, Vec<Claims<Actor>>> = HashMap::new();
+        for (host, actor) in actors {
+            items.entry(host.to_string()).or_default().push(Claims {
+                subject: actor.to_string(),
+                ..Default::default()
+            });
+        }
+        InventoryReport {
+            items,
+            errors: vec![],
+        }
+    }
+
+    fn status(tracker: &HealthTracker) -> HealthStatus {
+        tracker.health("Nhost", "Mecho").unwrap().status
+    }
+
+    #[test]
+    fn becomes_unhealthy_after_threshold_misses() {
+        let mut tracker = tracker();
+        tracker.apply_event(&started("Mecho", "Nhost"));
+
+        assert!(tracker.apply_probe(&probe(&[])).is_empty());
+        assert_eq!(status(&tracker), HealthStatus::Healthy);
+        assert_eq!(
+            tracker.apply_probe(&probe(&[])),
+            vec![BusEvent::ActorBecameUnhealthy {
+                actor: "Mecho".to_string(),
+                host: "Nhost".to_string(),
+            }]
+        );
+        assert_eq!(status(&tracker), HealthStatus::Unhealthy);
+        // The transition is only reported once
+        assert!(tracker.apply_probe(&probe(&[])).is_empty());
+    }
+
+    #[test]
+    fn becomes_healthy_after_threshold_hits() {
+        let mut tracker = tracker();
+        tracker.apply_event(&started("Mecho", "Nhost"));
+        tracker.apply_probe(&probe(&[]));
+        tracker.apply_probe(&probe(&[]));
+
+        let present = probe(&[("Nhost", "Mecho")]);
+        assert!(tracker.apply_probe(&present).is_empty());
+        assert_eq!(status(&tracker), HealthStatus::Unhealthy);
+        assert_eq!(
+            tracker.apply_probe(&present),
+            vec![BusEvent::ActorBecameHealthy {
+                actor: "Mecho".to_string(),
+                host: "Nhost".to_string(),
+            }]
+        );
+        assert_eq!(status(&tracker), HealthStatus::Healthy);
+    }
+
+    #[test]
+    fn miss_resets_consecutive_hits() {
+        let mut tracker = tracker();
+        tracker.apply_event(&started("Mecho", "Nhost"));
+        tracker.apply_probe(&probe(&[]));
+        tracker.apply_probe(&probe(&[]));
+
+        tracker.apply_probe(&probe(&[("Nhost", "Mecho")]));
+        assert_eq!(
+            tracker.health("Nhost", "Mecho").unwrap().consecutive_hits,
+            1
+        );
+        tracker.apply_probe(&probe(&[]));
+        let health = tracker.health("Nhost", "Mecho").unwrap();
+        assert_eq!(health.consecutive_hits, 0);
+        assert_eq!(health.consecutive_misses, 1);
+
+        assert!(tracker
+            .apply_probe(&probe(&[("Nhost", "Mecho")]))
+            .is_empty());
+        assert_eq!(status(&tracker), HealthStatus::Unhealthy);
+    }
+
+    #[test]
+    fn restart_of_unhealthy_actor_is_a_transition() {
+        let mut tracker = tracker();
+        tracker.apply_event(&started("Mecho", "Nhost"));
+        tracker.apply_probe(&probe(&[]));
+        tracker.apply_probe(&probe(&[]));
+
+        assert_eq!(
+            tracker.apply_event(&started("Mecho", "Nhost")),
+            vec![BusEvent::ActorBecameHealthy {
+                actor: "Mecho".to_string(),
+                host: "Nhost".to_string(),
+            }]
+        );
+        assert_eq!(status(&tracker), HealthStatus::Healthy);
+    }
+
+    #[test]
+    fn stopped_actors_are_no_longer_tracked() {
+        let mut tracker = tracker();
+        tracker.apply_event(&started("Mecho", "Nhost"));
+        tracker.apply_probe(&probe(&[]));
+
+        let transitions = tracker.apply_event(&BusEvent::ActorStopped {
+            actor: "Mecho".to_string(),
+            host: "Nhost".to_string(),
+        });
+        assert!(transitions.is_empty());
+        assert!(tracker.health("Nhost", "Mecho").is_none());
+        assert!(tracker.apply_probe(&probe(&[])).is_empty());
+    }
+
+    #[test]
+    fn probes_discover_running_actors() {
+        let mut tracker = tracker();
+        assert!(tracker
+            .apply_probe(&probe(&[("Nhost", "Mecho")]))
+            .is_empty());
+        assert_eq!(status(&tracker), HealthStatus::Healthy);
+    }
+
+    #[test]
+    fn undecoded_hosts_are_skipped() {
+        let mut tracker = tracker();
+        tracker.apply_event(&started("Mecho", "Nhost"));
+        tracker.apply_event(&started("Mkv", "Nother"));
+        let mut report = probe(&[]);
+        report.errors.push(InventoryReplyError {
+            host: Some("Nhost".to_string()),
+            subject: "_INBOX.probe".to_string(),
+            raw: b"{".to_vec(),
+            source: LatticeError::Timeout,
+        });
+
+        tracker.apply_probe(&report);
+        tracker.apply_probe(&report);
+        assert_eq!(
+            tracker.health("Nhost", "Mecho").unwrap(),
+            &ActorHealth::healthy()
+        );
+        assert_eq!(
+            tracker.health("Nother", "Mkv").unwrap().status,
+            HealthStatus::Unhealthy
+        );
+    }
+}
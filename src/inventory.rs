@@ -0,0 +1,227 @@
+use std::{collections::HashMap, fmt, time::Duration};
+
+use crate::{InventoryResponse, LatticeError, Result};
+
+/// The aggregated results of a lattice inventory probe. Every host replies to a probe independently,
+/// so a reply that cannot be decoded does not discard the others: the data from all of the good
+/// replies is aggregated into `items`, and each bad reply is recorded in `errors`
+#[derive(Debug)]
+pub struct InventoryReport<T> {
+    /// The data aggregated from every reply that could be decoded
+    pub items: T,
+    /// The replies that could not be decoded
+    pub errors: Vec<InventoryReplyError>,
+}
+
+impl<T: Default> Default for InventoryReport<T> {
+    fn default() -> Self {
+        InventoryReport {
+            items: T::default(),
+            errors: vec![],
+        }
+    }
+}
+
+impl<T> InventoryReport<T> {
+    /// Indicates whether every reply to the probe was decoded successfully
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Converts the report into its aggregated data, failing with the cause of the first bad reply if
+    /// the report is not complete
+    pub fn into_result(self) -> Result<T> {
+        match self.errors.into_iter().next() {
+            Some(e) => Err(e.source),
+            None => Ok(self.items),
+        }
+    }
+}
+
+/// A single decoded reply to a lattice inventory probe, or the reason it could not be decoded
+pub type InventoryReply<T> = std::result::Result<T, InventoryReplyError>;
+
+/// An error produced when a reply to a lattice inventory probe cannot be decoded
+#[derive(Debug)]
+pub struct InventoryReplyError {
+    /// The host that sent the reply, if the payload identifies it
+    pub host: Option<String>,
+    /// The subject on which the reply was received
+    pub subject: String,
+    /// The raw payload of the reply
+    pub raw: Vec<u8>,
+    /// The reason the payload could not be decoded
+    pub source: LatticeError,
+}
+
+impl InventoryReplyError {
+    /// Returns the raw reply for display
+    pub fn payload(&self) -> String {
+        String::from_utf8_lossy(&self.raw).to_string()
+    }
+}
+
+impl fmt::Display for InventoryReplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.host {
+            Some(ref host) => write!(
+                f,
+                "Failed to decode inventory reply from host {}: {}",
+                host, self.source
+            ),
+            None => write!(
+                f,
+                "Failed to decode inventory reply on {}: {}",
+                self.subject, self.source
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InventoryReplyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// The replies to a scatter-gather probe, ending once no reply has arrived within the timeout period
+pub(crate) struct ProbeReplies {
+    pub(crate) sub: nats::Subscription,
+    pub(crate) timeout: Duration,
+}
+
+impl Iterator for ProbeReplies {
+    type Item = nats::Message;
+
+    fn next(&mut self) -> Option<nats::Message> {
+        self.sub.next_timeout(self.timeout).ok()
+    }
+}
+
+/// Decodes a reply to an inventory probe. When the reply cannot be decoded, the replying host is
+/// recovered from the payload on a best-effort basis
+pub(crate) fn decode_reply(subject: &str, data: &[u8]) -> InventoryReply<InventoryResponse> {
+    serde_json::from_slice(data).map_err(|e| InventoryReplyError {
+        host: reply_host(data),
+        subject: subject.to_string(),
+        raw: data.to_vec(),
+        source: e.into(),
+    })
+}
+
+/// Aggregates per-host inventory replies, keyed by host ID
+pub(crate) fn aggregate<T>(
+    replies: impl IntoIterator<Item = InventoryReply<(String, Vec<T>)>>,
+) -> InventoryReport<HashMap<String, Vec<T>>> {
+    let mut report: InventoryReport<HashMap<String, Vec<T>>> = InventoryReport::default();
+    for reply in replies {
+        match reply {
+            Ok((host, items)) => report.items.entry(host).or_default().extend(items),
+            Err(e) => report.errors.push(e),
+        }
+    }
+    report
+}
+
+// Inventory responses are externally tagged, e.g. `{"Actors": {"host": "...", ...}}` or `{"Host": {"id": "...", ...}}`
+fn reply_host(data: &[u8]) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_slice(data).ok()?;
+    let inner = value.as_object()?.values().next()?;
+    inner
+        .get("host")
+        .or_else(|| inner.get("id"))
+        .and_then(serde_json::Value::as_str)
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn undecodable(host: Option<&str>) -> InventoryReplyError {
+        InventoryReplyError {
+            host: host.map(str::to_string),
+            subject: "_INBOX.probe".to_string(),
+            raw: vec![],
+            source: LatticeError::Timeout,
+        }
+    }
+
+    #[test]
+    fn valid_replies_decode() {
+        let reply = decode_reply(
+            "_INBOX.probe",
+            br#"{"Actors":{"host":"Nhost","actors":[]}}"#,
+        );
+        match reply {
+            Ok(InventoryResponse::Actors { host, actors }) => {
+                assert_eq!(host, "Nhost");
+                assert!(actors.is_empty());
+            }
+            other => panic!("expected an actors reply, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn host_is_recovered_from_undecodable_actor_replies() {
+        let data = br#"{"Actors":{"host":"Nhost","actors":"not a list"}}"#;
+        let e = decode_reply("_INBOX.probe", data).unwrap_err();
+        assert_eq!(e.host.as_deref(), Some("Nhost"));
+        assert_eq!(e.subject, "_INBOX.probe");
+        assert_eq!(e.raw, data.to_vec());
+        assert!(matches!(e.source, LatticeError::Serialization(_)));
+    }
+
+    #[test]
+    fn host_is_recovered_from_undecodable_host_replies() {
+        let e = decode_reply(
+            "_INBOX.probe",
+            br#"{"Host":{"id":"Nhost","uptime_ms":"soon"}}"#,
+        )
+        .unwrap_err();
+        assert_eq!(e.host.as_deref(), Some("Nhost"));
+    }
+
+    #[test]
+    fn non_json_replies_have_no_host() {
+        let e = decode_reply("_INBOX.probe", b"garbage").unwrap_err();
+        assert_eq!(e.host, None);
+        assert_eq!(e.payload(), "garbage");
+    }
+
+    #[test]
+    fn replies_from_the_same_host_are_merged() {
+        let report = aggregate(vec![
+            Ok(("Nhost".to_string(), vec![1, 2])),
+            Err(undecodable(None)),
+            Ok(("Nother".to_string(), vec![3])),
+            Ok(("Nhost".to_string(), vec![4])),
+        ]);
+        assert_eq!(report.items["Nhost"], vec![1, 2, 4]);
+        assert_eq!(report.items["Nother"], vec![3]);
+        assert_eq!(report.errors.len(), 1);
+        assert!(!report.is_complete());
+    }
+
+    #[test]
+    fn complete_reports_convert_to_their_items() {
+        let report = aggregate(vec![Ok(("Nhost".to_string(), vec![1]))]);
+        assert!(report.is_complete());
+        assert_eq!(report.into_result().unwrap()["Nhost"], vec![1]);
+    }
+
+    #[test]
+    fn incomplete_reports_convert_to_the_first_error() {
+        let mut first = undecodable(Some("Nbad"));
+        first.source = LatticeError::InvalidEvent("first".to_string());
+        let report = aggregate(vec![
+            Ok(("Nhost".to_string(), vec![1])),
+            Err(first),
+            Err(undecodable(Some("Nworse"))),
+        ]);
+        match report.into_result() {
+            Err(LatticeError::InvalidEvent(s)) => assert_eq!(s, "first"),
+            other => panic!("expected the first error, got {:?}", other),
+        }
+    }
+}
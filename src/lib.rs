@@ -9,22 +9,60 @@ extern crate log;
 #[macro_use]
 extern crate serde;
 
-use std::{collections::HashMap, path::PathBuf, time::Duration};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 use crossbeam::Sender;
+use serde::de::DeserializeOwned;
 use wascap::prelude::*;
 
+#[cfg(feature = "async")]
+pub use async_client::AsyncClient;
+pub use auction::AuctionOptions;
+use auction::{retain_eligible, BidCollector};
+pub use builder::ClientBuilder;
+pub use constraints::{Constraint, ConstraintMode, ConstraintSet};
 use controlplane::{
     LaunchAck, LaunchAuctionRequest, LaunchAuctionResponse, LaunchCommand, TerminateCommand,
 };
-pub use events::{BusEvent, CloudEvent};
+pub use errors::{LatticeError, Result};
+use events::decode_event;
+pub use events::{
+    BusEvent, BusEventKind, CloudEvent, CloudEventBuilder, DataFormat, EventDecodeError,
+    EventFilter,
+};
+use inventory::{aggregate, decode_reply, ProbeReplies};
+pub use inventory::{InventoryReply, InventoryReplyError, InventoryReport};
+pub use placement::{
+    FirstResponderStrategy, LabelPreferenceStrategy, LeastLoadedStrategy, Placement,
+    PlacementCandidate, PlacementPlan, PlacementStrategy, RandomStrategy, RoundRobinStrategy,
+};
+pub use scaling::{ScaleOptions, ScaleReport, ScaleShortfall, SpreadPolicy};
+pub use state::{LatticeSnapshot, LatticeState};
+pub use subscription::EventSubscription;
 
 use crate::controlplane::{
-    LaunchProviderCommand, ProviderAuctionRequest, ProviderAuctionResponse, ProviderLaunchAck,
+    LatticeSubjects, LaunchProviderCommand, ProviderAuctionRequest, ProviderAuctionResponse,
+    ProviderLaunchAck, TerminateProviderCommand,
 };
 
+#[cfg(feature = "async")]
+mod async_client;
+mod auction;
+mod builder;
+mod constraints;
 pub mod controlplane;
+mod errors;
 mod events;
+pub mod health;
+mod inventory;
+mod placement;
+mod scaling;
+mod state;
+mod subscription;
 
 pub const INVENTORY_ACTORS: &str = "inventory.actors";
 pub const INVENTORY_HOSTS: &str = "inventory.hosts";
@@ -86,7 +124,8 @@ pub struct Binding {
     pub configuration: HashMap<String, String>,
 }
 
-/// A client for interacting with the lattice
+/// A client for interacting with the lattice. Clones share the same underlying connection
+#[derive(Clone)]
 pub struct Client {
     nc: nats::Connection,
     namespace: Option<String>,
@@ -95,18 +134,40 @@ pub struct Client {
 
 impl Client {
     /// Creates a new lattice client, connecting to the NATS server at the
-    /// given host with an optional set of credentials (JWT auth)
+    /// given host with an optional set of credentials (JWT auth). This will panic
+    /// if the connection cannot be established, use [try_new](#method.try_new) to
+    /// handle connection failures
     pub fn new(
         host: &str,
         credsfile: Option<PathBuf>,
         call_timeout: Duration,
         namespace: Option<String>,
     ) -> Self {
-        Client {
-            nc: get_connection(host, credsfile),
-            timeout: call_timeout,
-            namespace,
+        Client::try_new(host, credsfile, call_timeout, namespace).unwrap()
+    }
+
+    /// Creates a new lattice client, connecting to the NATS server at the given host with
+    /// an optional set of credentials (JWT auth). Returns `LatticeError::Connection` if the
+    /// connection cannot be established
+    pub fn try_new(
+        host: &str,
+        credsfile: Option<PathBuf>,
+        call_timeout: Duration,
+        namespace: Option<String>,
+    ) -> Result<Self> {
+        let mut builder = ClientBuilder::new().server(host).call_timeout(call_timeout);
+        if let Some(creds) = credsfile {
+            builder = builder.credentials(creds);
+        }
+        if let Some(ns) = namespace {
+            builder = builder.namespace(&ns);
         }
+        builder.build()
+    }
+
+    /// Creates a builder used to configure the connection options of a new lattice client
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
     }
 
     pub fn with_connection(
@@ -123,166 +184,529 @@ impl Client {
 
     /// Retrieves the list of all hosts running within the lattice. If it takes a host longer
     /// than the call timeout period to reply to the probe, it will not be included in the list
-    /// of hosts.
-    pub fn get_hosts(&self) -> std::result::Result<Vec<HostProfile>, Box<dyn std::error::Error>> {
-        let mut hosts = vec![];
-        let sub = self
-            .nc
-            .request_multi(self.gen_subject(INVENTORY_HOSTS).as_ref(), &[])?;
-        for msg in sub.timeout_iter(self.timeout) {
-            let ir: InventoryResponse = serde_json::from_slice(&msg.data)?;
-            if let InventoryResponse::Host(h) = ir {
-                hosts.push(h);
+    /// of hosts. Replies that cannot be decoded are recorded in the report's errors rather than
+    /// failing the whole probe
+    pub fn get_hosts(&self) -> Result<InventoryReport<Vec<HostProfile>>> {
+        let mut report: InventoryReport<Vec<HostProfile>> = InventoryReport::default();
+        for reply in self.stream_hosts()? {
+            match reply {
+                Ok(host) => report.items.push(host),
+                Err(e) => report.errors.push(e),
             }
         }
-        Ok(hosts)
+        Ok(report)
     }
 
     /// Retrieves a list of all bindings from actors to capabilities within the lattice (provided
     /// the host responds to the probe within the client timeout period)
-    pub fn get_bindings(
+    pub fn get_bindings(&self) -> Result<InventoryReport<HashMap<String, Vec<Binding>>>> {
+        Ok(aggregate(self.stream_bindings()?))
+    }
+
+    /// Retrieves the list of all actors currently running within the lattice (as discovered within
+    /// the client timeout period)
+    pub fn get_actors(&self) -> Result<InventoryReport<HashMap<String, Vec<Claims<Actor>>>>> {
+        Ok(aggregate(self.stream_actors()?))
+    }
+
+    /// Retrieves the list of all capabilities within the lattice (discovery limited by the client timeout period)
+    pub fn get_capabilities(
         &self,
-    ) -> std::result::Result<HashMap<String, Vec<Binding>>, Box<dyn std::error::Error>> {
-        let mut host_bindings = HashMap::new();
+    ) -> Result<InventoryReport<HashMap<String, Vec<HostedCapability>>>> {
+        Ok(aggregate(self.stream_capabilities()?))
+    }
 
-        let sub = self
-            .nc
-            .request_multi(self.gen_subject(INVENTORY_BINDINGS).as_ref(), &[])?;
-        for msg in sub.timeout_iter(self.timeout) {
-            let ir: InventoryResponse = serde_json::from_slice(&msg.data)?;
-            if let InventoryResponse::Bindings { bindings: b, host } = ir {
-                host_bindings
-                    .entry(host)
-                    .and_modify(|e: &mut Vec<Binding>| e.extend_from_slice(&b))
-                    .or_insert(b.clone());
+    /// Probes the lattice for hosts, yielding each host's profile as soon as its reply arrives. The
+    /// iterator ends once no reply has arrived within the call timeout period. A reply that cannot be
+    /// decoded is yielded as an error without ending the iteration
+    pub fn stream_hosts(&self) -> Result<impl Iterator<Item = InventoryReply<HostProfile>>> {
+        Ok(self
+            .stream_inventory(INVENTORY_HOSTS)?
+            .filter_map(|reply| match reply {
+                Ok(InventoryResponse::Host(h)) => Some(Ok(h)),
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            }))
+    }
+
+    /// Probes the lattice for bindings, yielding each host's bindings as soon as its reply arrives
+    pub fn stream_bindings(
+        &self,
+    ) -> Result<impl Iterator<Item = InventoryReply<(String, Vec<Binding>)>>> {
+        Ok(self
+            .stream_inventory(INVENTORY_BINDINGS)?
+            .filter_map(|reply| match reply {
+                Ok(InventoryResponse::Bindings { host, bindings }) => Some(Ok((host, bindings))),
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            }))
+    }
+
+    /// Probes the lattice for actors, yielding each host's actors as soon as its reply arrives
+    pub fn stream_actors(
+        &self,
+    ) -> Result<impl Iterator<Item = InventoryReply<(String, Vec<Claims<Actor>>)>>> {
+        Ok(self
+            .stream_inventory(INVENTORY_ACTORS)?
+            .filter_map(|reply| match reply {
+                Ok(InventoryResponse::Actors { host, actors }) => Some(Ok((host, actors))),
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            }))
+    }
+
+    /// Probes the lattice for capabilities, yielding each host's capabilities as soon as its reply arrives
+    pub fn stream_capabilities(
+        &self,
+    ) -> Result<impl Iterator<Item = InventoryReply<(String, Vec<HostedCapability>)>>> {
+        Ok(self
+            .stream_inventory(INVENTORY_CAPABILITIES)?
+            .filter_map(|reply| match reply {
+                Ok(InventoryResponse::Capabilities { host, capabilities }) => {
+                    Some(Ok((host, capabilities)))
+                }
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            }))
+    }
+
+    /// Invokes the callback with each host's profile as soon as its reply arrives, returning once the
+    /// probe has completed
+    pub fn for_each_host<F>(&self, callback: F) -> Result<()>
+    where
+        F: FnMut(InventoryReply<HostProfile>),
+    {
+        self.stream_hosts()?.for_each(callback);
+        Ok(())
+    }
+
+    /// Invokes the callback with each host's bindings as soon as its reply arrives, returning once the
+    /// probe has completed
+    pub fn for_each_binding<F>(&self, callback: F) -> Result<()>
+    where
+        F: FnMut(InventoryReply<(String, Vec<Binding>)>),
+    {
+        self.stream_bindings()?.for_each(callback);
+        Ok(())
+    }
+
+    /// Invokes the callback with each host's actors as soon as its reply arrives, returning once the
+    /// probe has completed
+    pub fn for_each_actor<F>(&self, callback: F) -> Result<()>
+    where
+        F: FnMut(InventoryReply<(String, Vec<Claims<Actor>>)>),
+    {
+        self.stream_actors()?.for_each(callback);
+        Ok(())
+    }
+
+    /// Invokes the callback with each host's capabilities as soon as its reply arrives, returning once
+    /// the probe has completed
+    pub fn for_each_capability<F>(&self, callback: F) -> Result<()>
+    where
+        F: FnMut(InventoryReply<(String, Vec<HostedCapability>)>),
+    {
+        self.stream_capabilities()?.for_each(callback);
+        Ok(())
+    }
+
+    fn collect_bids<T: DeserializeOwned>(
+        &self,
+        subject: &str,
+        payload: &[u8],
+        options: &AuctionOptions,
+    ) -> Result<Vec<T>> {
+        let sub = self.nc.request_multi(subject, payload)?;
+        let mut collector = BidCollector::open(options);
+        while let Some(wait) = collector.next_wait() {
+            match sub.next_timeout(wait) {
+                Ok(msg) => collector.push(&msg.data)?,
+                Err(_) => break,
             }
         }
-        Ok(host_bindings)
+        collector.finish()
     }
 
-    /// Retrieves the list of all actors currently running within the lattice (as discovered within
-    /// the client timeout period)
-    pub fn get_actors(
+    fn plan(
         &self,
-    ) -> std::result::Result<HashMap<String, Vec<Claims<Actor>>>, Box<dyn std::error::Error>> {
-        let mut host_actors = HashMap::new();
+        actor_id: &str,
+        candidates: Vec<PlacementCandidate>,
+        strategy: &mut dyn PlacementStrategy,
+    ) -> Result<PlacementPlan> {
+        let eligible: Vec<String> = candidates
+            .iter()
+            .filter(|c| c.eligible)
+            .map(|c| c.host_id.to_string())
+            .collect();
+        let winner = if eligible.is_empty() {
+            None
+        } else {
+            Some(strategy.select(self, &eligible)?)
+        };
+        Ok(PlacementPlan {
+            actor_id: actor_id.to_string(),
+            candidates,
+            winner,
+        })
+    }
 
-        let sub = self
-            .nc
-            .request_multi(self.gen_subject(INVENTORY_ACTORS).as_ref(), &[])?;
-        for msg in sub.timeout_iter(self.timeout) {
-            let ir: InventoryResponse = serde_json::from_slice(&msg.data)?;
-            if let InventoryResponse::Actors { host, actors } = ir {
-                host_actors
-                    .entry(host)
-                    .and_modify(|e: &mut Vec<Claims<Actor>>| e.extend_from_slice(&actors))
-                    .or_insert(actors.clone());
-            }
+    fn verify_bids<T>(
+        &self,
+        bids: Vec<T>,
+        constraints: &ConstraintSet,
+        host_id: impl Fn(&T) -> &String,
+    ) -> Result<Vec<T>> {
+        if !constraints.requires_client_evaluation() {
+            return Ok(bids);
         }
-        Ok(host_actors)
+        let eligible: Vec<String> = self
+            .hosts_matching(constraints)?
+            .into_iter()
+            .map(|h| h.id)
+            .collect();
+        retain_eligible(bids, &eligible, host_id)
     }
 
-    /// Retrieves the list of all capabilities within the lattice (discovery limited by the client timeout period)
-    pub fn get_capabilities(
+    fn stream_inventory(
         &self,
-    ) -> std::result::Result<HashMap<String, Vec<HostedCapability>>, Box<dyn std::error::Error>>
-    {
-        let mut host_caps = HashMap::new();
+        subject: &str,
+    ) -> Result<impl Iterator<Item = InventoryReply<InventoryResponse>>> {
         let sub = self
             .nc
-            .request_multi(self.gen_subject(INVENTORY_CAPABILITIES).as_ref(), &[])?;
-        for msg in sub.timeout_iter(self.timeout) {
-            let ir: InventoryResponse = serde_json::from_slice(&msg.data)?;
-            if let InventoryResponse::Capabilities { host, capabilities } = ir {
-                host_caps
-                    .entry(host)
-                    .and_modify(|e: &mut Vec<HostedCapability>| e.extend_from_slice(&capabilities))
-                    .or_insert(capabilities.clone());
-            }
+            .request_multi(self.gen_subject(subject).as_ref(), [])?;
+        Ok(ProbeReplies {
+            sub,
+            timeout: self.timeout,
         }
-        Ok(host_caps)
+        .map(|msg| decode_reply(&msg.subject, &msg.data)))
     }
 
-    /// Watches the lattice for bus events. This will create a subscription in a background thread, so callers
-    /// are responsible for ensuring their process remains alive however long is appropriate. Pass the sender
-    /// half of a channel to receive the events
-    pub fn watch_events(&self, sender: Sender<BusEvent>) -> Result<(), Box<dyn std::error::Error>> {
-        let _sub = self
-            .nc
-            .subscribe(self.gen_subject(EVENTS).as_ref())?
-            .with_handler(move |msg| {
-                let ce: CloudEvent = serde_json::from_slice(&msg.data).unwrap();
-                let be: BusEvent = serde_json::from_str(&ce.data).unwrap();
-                let _ = sender.send(be);
-                Ok(())
-            });
+    /// Watches the lattice for bus events. Events are delivered to the sender half of the given channel from a
+    /// background thread for as long as the returned [EventSubscription](struct.EventSubscription.html) is held,
+    /// so callers must keep the handle alive for as long as they wish to receive events. Messages that cannot be
+    /// decoded are delivered through the channel as an `EventDecodeError` rather than interrupting the subscription
+    pub fn watch_events(
+        &self,
+        sender: Sender<std::result::Result<BusEvent, EventDecodeError>>,
+    ) -> Result<EventSubscription> {
+        self.watch_events_filtered(EventFilter::new(), sender)
+    }
+
+    /// Watches the lattice for bus events that satisfy the given filter. All hosts publish their events on a single
+    /// subject, so every event is still received from the NATS server and the filter is applied client-side, in
+    /// the subscription's background thread, before events are delivered to the channel. This spares callers
+    /// from discarding irrelevant events themselves, but not the network traffic. Messages that cannot be
+    /// decoded are always delivered as an `EventDecodeError`
+    pub fn watch_events_filtered(
+        &self,
+        filter: EventFilter,
+        sender: Sender<std::result::Result<BusEvent, EventDecodeError>>,
+    ) -> Result<EventSubscription> {
+        let sub = self.nc.subscribe(self.gen_subject(EVENTS).as_ref())?;
+        Ok(EventSubscription::start(sub, filter, sender))
+    }
+
+    /// Publishes an event onto the lattice bus, wrapped in a CloudEvent envelope using the legacy data format
+    /// understood by all lattice consumers. This allows external services to feed higher-order events such
+    /// as `ActorBecameHealthy` to the rest of the lattice
+    pub fn publish_event(&self, event: BusEvent) -> Result<()> {
+        self.publish_cloud_event(&CloudEvent::from(event))
+    }
+
+    /// Publishes a CloudEvent envelope onto the lattice bus, use a [CloudEventBuilder](struct.CloudEventBuilder.html)
+    /// to control the envelope attributes. Returns `LatticeError::InvalidEvent` without publishing if the
+    /// envelope does not pass [validation](struct.CloudEvent.html#method.validate)
+    pub fn publish_cloud_event(&self, event: &CloudEvent) -> Result<()> {
+        event.validate()?;
+        self.nc
+            .publish(&self.gen_subject(EVENTS), &serde_json::to_vec(event)?)?;
+        let _ = self.nc.flush();
         Ok(())
     }
 
     /// Performs an auction among all hosts on the lattice, requesting that the given actor be launched (loaded+started)
     /// on a suitable host as described by the set of constraints. Only hosts that believe they can launch the actor
     /// will reply. In other words, there will be no negative responses in the result vector, only a list of suitable
-    /// hosts. The actor to be launched is identified by an OCI registry reference
+    /// hosts. The actor to be launched is identified by an OCI registry reference. If no hosts bid on the
+    /// auction, this returns `LatticeError::EmptyAuction`
     pub fn perform_actor_launch_auction(
         &self,
         actor_id: &str,
         constraints: HashMap<String, String>,
-    ) -> Result<Vec<LaunchAuctionResponse>, Box<dyn std::error::Error>> {
-        let mut results = vec![];
-        let req = LaunchAuctionRequest::new(actor_id, constraints);
-        let sub = self.nc.request_multi(
-            self.gen_subject(&format!(
-                "{}.{}",
-                controlplane::CPLANE_PREFIX,
-                controlplane::AUCTION_REQ
-            ))
-            .as_ref(),
+    ) -> Result<Vec<LaunchAuctionResponse>> {
+        self.perform_actor_launch_auction_with(
+            actor_id,
+            &constraints.into(),
+            &AuctionOptions::default(),
+        )
+    }
+
+    /// Performs an actor launch auction with the given [ConstraintSet](struct.ConstraintSet.html), collecting
+    /// bids as described by the given [AuctionOptions](struct.AuctionOptions.html). Constraints that hosts
+    /// cannot evaluate themselves are evaluated against the labels reported by `get_hosts`, and bids from hosts
+    /// that do not satisfy them (or that do not report their labels in time) are discarded
+    pub fn perform_actor_launch_auction_with(
+        &self,
+        actor_id: &str,
+        constraints: &ConstraintSet,
+        options: &AuctionOptions,
+    ) -> Result<Vec<LaunchAuctionResponse>> {
+        let mut req = LaunchAuctionRequest::new(actor_id, constraints.equality_subset()?);
+        req.expressions = constraints.expressions();
+        let bids = self.collect_bids(
+            &self.gen_auction_subject(),
             &serde_json::to_vec(&req)?,
+            options,
         )?;
-        for msg in sub.timeout_iter(Duration::from_secs(AUCTION_TIMEOUT_SECONDS)) {
-            let resp: LaunchAuctionResponse = serde_json::from_slice(&msg.data)?;
-            results.push(resp);
-        }
-        Ok(results)
+        self.verify_bids(bids, constraints, |b: &LaunchAuctionResponse| &b.host_id)
     }
 
     /// Performs an auction among all hosts on the lattice, requesting that the given capability provider
     /// (indicated by OCI image reference) be loaded/started. Hosts that believe they can host the
-    /// provider given the constraints will respond to the auction
+    /// provider given the constraints will respond to the auction. If no hosts bid on the auction, this
+    /// returns `LatticeError::EmptyAuction`
     pub fn perform_provider_launch_auction(
         &self,
         provider_ref: &str,
         binding_name: &str,
         constraints: HashMap<String, String>,
-    ) -> Result<Vec<ProviderAuctionResponse>, Box<dyn std::error::Error>> {
-        let mut results = vec![];
-        let req = ProviderAuctionRequest::new(provider_ref, binding_name, constraints);
-        let sub = self.nc.request_multi(
-            self.gen_subject(&format!(
-                "{}.{}",
-                controlplane::CPLANE_PREFIX,
-                controlplane::PROVIDER_AUCTION_REQ
-            ))
-            .as_ref(),
+    ) -> Result<Vec<ProviderAuctionResponse>> {
+        self.perform_provider_launch_auction_with(
+            provider_ref,
+            binding_name,
+            &constraints.into(),
+            &AuctionOptions::default(),
+        )
+    }
+
+    /// Performs a provider launch auction with the given [ConstraintSet](struct.ConstraintSet.html), collecting
+    /// bids as described by the given [AuctionOptions](struct.AuctionOptions.html). Constraints are verified
+    /// against host labels in the same way as for [actor auctions](#method.perform_actor_launch_auction_with)
+    pub fn perform_provider_launch_auction_with(
+        &self,
+        provider_ref: &str,
+        binding_name: &str,
+        constraints: &ConstraintSet,
+        options: &AuctionOptions,
+    ) -> Result<Vec<ProviderAuctionResponse>> {
+        let mut req =
+            ProviderAuctionRequest::new(provider_ref, binding_name, constraints.equality_subset()?);
+        req.expressions = constraints.expressions();
+        let bids = self.collect_bids(
+            &self.gen_provider_auction_subject(),
             &serde_json::to_vec(&req)?,
+            options,
         )?;
-        for msg in sub.timeout_iter(Duration::from_secs(AUCTION_TIMEOUT_SECONDS)) {
-            let resp: ProviderAuctionResponse = serde_json::from_slice(&msg.data)?;
-            results.push(resp);
+        self.verify_bids(bids, constraints, |b: &ProviderAuctionResponse| &b.host_id)
+    }
+
+    /// Plans the placement of the given actor without launching it. An auction is held as described by the
+    /// given options, the bids are checked against the constraints, and the strategy chooses a winner from
+    /// among the eligible bidders. Hosts that do not bid are listed as ineligible candidates. Auction requests
+    /// do not cause hosts to launch anything, so no `LaunchCommand` is ever sent
+    pub fn plan_actor_placement(
+        &self,
+        actor_id: &str,
+        constraints: &ConstraintSet,
+        options: &AuctionOptions,
+        strategy: &mut dyn PlacementStrategy,
+    ) -> Result<PlacementPlan> {
+        let hosts = self.get_hosts()?.items;
+        let mut req = LaunchAuctionRequest::new(actor_id, constraints.equality_subset()?);
+        req.expressions = constraints.expressions();
+        let bids: Vec<String> = match self.collect_bids::<LaunchAuctionResponse>(
+            &self.gen_auction_subject(),
+            &serde_json::to_vec(&req)?,
+            options,
+        ) {
+            Ok(bids) => bids.into_iter().map(|b| b.host_id).collect(),
+            Err(LatticeError::EmptyAuction) => vec![],
+            Err(e) => return Err(e),
+        };
+        let mut host_ids = bids.clone();
+        host_ids.extend(
+            hosts
+                .iter()
+                .map(|h| h.id.to_string())
+                .filter(|id| !bids.contains(id)),
+        );
+        let candidates = host_ids
+            .into_iter()
+            .map(|host_id| {
+                let labels = hosts
+                    .iter()
+                    .find(|h| h.id == host_id)
+                    .map(|h| h.labels.clone());
+                let (eligible, reason) =
+                    bid_eligibility(constraints, bids.contains(&host_id), labels.as_ref());
+                PlacementCandidate {
+                    host_id,
+                    labels: labels.unwrap_or_default(),
+                    eligible,
+                    reason,
+                }
+            })
+            .collect();
+        self.plan(actor_id, candidates, strategy)
+    }
+
+    /// Plans the placement of the given actor by evaluating the constraints against the labels of every host
+    /// reported by `get_hosts`, without holding an auction. Hosts may still decline to bid on a real auction,
+    /// for example for lack of capacity, so this is a less faithful preview than
+    /// [plan_actor_placement](#method.plan_actor_placement)
+    pub fn plan_actor_placement_from_labels(
+        &self,
+        actor_id: &str,
+        constraints: &ConstraintSet,
+        strategy: &mut dyn PlacementStrategy,
+    ) -> Result<PlacementPlan> {
+        let candidates = self
+            .get_hosts()?
+            .items
+            .into_iter()
+            .map(|h| {
+                let (eligible, reason) =
+                    candidate_eligibility(constraints, &h.labels, "satisfies all constraints");
+                PlacementCandidate {
+                    host_id: h.id,
+                    labels: h.labels,
+                    eligible,
+                    reason,
+                }
+            })
+            .collect();
+        self.plan(actor_id, candidates, strategy)
+    }
+
+    /// Scales the given actor to the requested number of replicas across the lattice, preferring hosts not
+    /// already running it. Replicas are counted by public key, which cannot be derived from the OCI reference
+    /// since neither launch acknowledgements nor actor claims carry it, so both must be supplied
+    pub fn scale_actor(
+        &self,
+        actor_ref: &str,
+        actor_key: &str,
+        replicas: usize,
+        constraints: &ConstraintSet,
+    ) -> Result<ScaleReport> {
+        self.scale_actor_with(
+            actor_ref,
+            actor_key,
+            replicas,
+            constraints,
+            &ScaleOptions::default(),
+            &mut FirstResponderStrategy,
+        )
+    }
+
+    /// Scales the given actor as [scale_actor](#method.scale_actor) does, with the strategy choosing among the
+    /// bidders allowed by the spread policy. Scaling stops at the first auction without a suitable host or the
+    /// first failed request, and the report records why. Surplus replicas are stopped on the most crowded hosts
+    pub fn scale_actor_with(
+        &self,
+        actor_ref: &str,
+        actor_key: &str,
+        replicas: usize,
+        constraints: &ConstraintSet,
+        options: &ScaleOptions,
+        strategy: &mut dyn PlacementStrategy,
+    ) -> Result<ScaleReport> {
+        let mut previous: Vec<String> = vec![];
+        for (host, actors) in self.get_actors()?.into_result()? {
+            for _ in actors.iter().filter(|a| a.subject == actor_key) {
+                previous.push(host.to_string());
+            }
         }
-        Ok(results)
+        let mut report = ScaleReport {
+            actor: actor_key.to_string(),
+            target: replicas,
+            previous,
+            launched: vec![],
+            stopped: vec![],
+            shortfall: None,
+        };
+        let failed = |host_id: Option<&str>, e: LatticeError| ScaleShortfall::Failed {
+            host_id: host_id.map(str::to_string),
+            error: e.to_string(),
+        };
+
+        while report.replicas() < replicas {
+            let bids: Vec<String> = match self.perform_actor_launch_auction_with(
+                actor_ref,
+                constraints,
+                &options.auction,
+            ) {
+                Ok(bids) => bids.into_iter().map(|b| b.host_id).collect(),
+                Err(LatticeError::EmptyAuction) => {
+                    report.shortfall = Some(ScaleShortfall::NoBids);
+                    break;
+                }
+                Err(e) => {
+                    report.shortfall = Some(failed(None, e));
+                    break;
+                }
+            };
+            let occupied: Vec<String> = report
+                .previous
+                .iter()
+                .chain(&report.launched)
+                .cloned()
+                .collect();
+            let candidates = options.spread.candidates(&bids, &occupied);
+            if candidates.is_empty() {
+                report.shortfall = Some(ScaleShortfall::NoVacantHosts);
+                break;
+            }
+            let host_id = match strategy.select(self, &candidates) {
+                Ok(placement) => placement.host_id,
+                Err(e) => {
+                    report.shortfall = Some(failed(None, e));
+                    break;
+                }
+            };
+            if let Err(e) = self.launch_actor_on_host(actor_ref, &host_id) {
+                report.shortfall = Some(failed(Some(&host_id), e));
+                break;
+            }
+            report.launched.push(host_id);
+        }
+
+        let mut remaining: HashMap<String, usize> = HashMap::new();
+        for host_id in &report.previous {
+            *remaining.entry(host_id.to_string()).or_default() += 1;
+        }
+        while report.replicas() > replicas {
+            let host_id = match scaling::most_crowded(&remaining) {
+                Some(host_id) => host_id,
+                None => break,
+            };
+            if let Err(e) = self.stop_actor_on_host(actor_key, &host_id) {
+                report.shortfall = Some(failed(Some(&host_id), e));
+                break;
+            }
+            if let Some(count) = remaining.get_mut(&host_id) {
+                *count -= 1;
+            }
+            report.stopped.push(host_id);
+        }
+        Ok(report)
+    }
+
+    /// Retrieves the hosts within the lattice whose labels satisfy the given constraints
+    pub fn hosts_matching(&self, constraints: &ConstraintSet) -> Result<Vec<HostProfile>> {
+        Ok(constraints.matching_hosts(self.get_hosts()?.items))
     }
 
     /// After collecting the results of a provider launch auction, a "winner" from among the hosts
     /// can be selected and told to launch the given provider. The provider's bytes will be retrieved
     /// from the OCI registry. This function does _not_ confirm successful launch, only receipt
-    /// of the launch request.
+    /// of the launch request. An acknowledgement that does not match the request results in a
+    /// `LatticeError::UnexpectedAck`
     pub fn launch_provider_on_host(
         &self,
         provider_ref: &str,
         host_id: &str,
         binding_name: &str,
-    ) -> Result<ProviderLaunchAck, Box<dyn std::error::Error>> {
+    ) -> Result<ProviderLaunchAck> {
         let msg = LaunchProviderCommand {
             provider_ref: provider_ref.to_string(),
             binding_name: binding_name.to_string(),
@@ -297,18 +721,17 @@ impl Client {
                 )?
                 .data,
         )?;
+        if ack.provider_ref != provider_ref || ack.host != host_id {
+            return Err(LatticeError::UnexpectedAck(format!("{:?}", ack)));
+        }
         Ok(ack)
     }
 
     /// After collecting the results of a launch auction, a "winner" from among the hosts can be selected and
     /// told to launch a given actor. Note that the actor's bytes will be retrieved from the OCI registry.
     /// This function does _not_ confirm successful launch, only that the target host acknowledged the request
-    /// to launch.
-    pub fn launch_actor_on_host(
-        &self,
-        actor_id: &str,
-        host_id: &str,
-    ) -> Result<LaunchAck, Box<dyn std::error::Error>> {
+    /// to launch. An acknowledgement that does not match the request results in a `LatticeError::UnexpectedAck`
+    pub fn launch_actor_on_host(&self, actor_id: &str, host_id: &str) -> Result<LaunchAck> {
         let msg = LaunchCommand {
             actor_id: actor_id.to_string(),
         };
@@ -322,17 +745,45 @@ impl Client {
                 )?
                 .data,
         )?;
+        if ack.actor_id != actor_id || ack.host != host_id {
+            return Err(LatticeError::UnexpectedAck(format!("{:?}", ack)));
+        }
         Ok(ack)
     }
 
-    /// Sends a command to the specified host telling it to terminate an actor. The success of this command indicates
-    /// a successful publication, and not necessarily a successful remote actor termination. Monitor the lattice
-    /// events to see if the actor was successfully terminated
-    pub fn stop_actor_on_host(
+    /// Tells the target host to launch a given actor and then waits for the host to report that the actor
+    /// has started. The events subscription is created before the launch command is sent, so the
+    /// confirmation cannot be missed. Returns `LatticeError::LaunchFailed` if the host reports the actor as
+    /// stopped instead, or `LatticeError::Timeout` if neither event is observed within the given period.
+    ///
+    /// Lattice events identify actors by public key, while the launch command and its acknowledgement only
+    /// carry the OCI reference, so the actor's public key must be supplied to recognize its events among
+    /// those of other actors on the same host. When the actor is launched by public key, pass it as both
+    pub fn launch_actor_and_wait(
         &self,
-        actor_id: &str,
+        actor_ref: &str,
+        actor_key: &str,
         host_id: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+        timeout: Duration,
+    ) -> Result<LaunchAck> {
+        let sub = self.nc.subscribe(&self.gen_subject(EVENTS))?;
+        let ack = self.launch_actor_on_host(actor_ref, host_id)?;
+        let event = wait_for_event(&sub, timeout, |e| {
+            actor_lifecycle(e, actor_key, host_id).is_some()
+        })?;
+        match actor_lifecycle(&event, actor_key, host_id) {
+            Some(true) => Ok(ack),
+            _ => Err(LatticeError::LaunchFailed(format!(
+                "Actor {} stopped on host {} before it finished starting",
+                actor_key, host_id
+            ))),
+        }
+    }
+
+    /// Sends a command to the specified host telling it to terminate an actor. The success of this command indicates
+    /// a successful publication, and not necessarily a successful remote actor termination. Use
+    /// [stop_actor_and_wait](#method.stop_actor_and_wait) to confirm the actor was terminated
+    pub fn stop_actor_on_host(&self, actor_id: &str, host_id: &str) -> Result<()> {
         let msg = TerminateCommand {
             actor_id: actor_id.to_string(),
         };
@@ -344,20 +795,239 @@ impl Client {
         Ok(())
     }
 
-    fn gen_subject(&self, subject: &str) -> String {
-        match self.namespace.as_ref() {
-            Some(s) => format!("{}.wasmbus.{}", s, subject),
-            None => format!("wasmbus.{}", subject),
-        }
+    /// Sends a command to the specified host telling it to terminate an actor, then waits for the host to
+    /// publish the corresponding `ActorStopped` event. Lattice events identify actors by public key, so the
+    /// actor's public key must be supplied to recognize the event when the actor is identified by OCI
+    /// reference. Returns `LatticeError::Timeout` if the event is not observed within the given period
+    pub fn stop_actor_and_wait(
+        &self,
+        actor_id: &str,
+        actor_key: &str,
+        host_id: &str,
+        timeout: Duration,
+    ) -> Result<()> {
+        let sub = self.nc.subscribe(&self.gen_subject(EVENTS))?;
+        self.stop_actor_on_host(actor_id, host_id)?;
+        wait_for_event(&sub, timeout, |e| {
+            actor_lifecycle(e, actor_key, host_id) == Some(false)
+        })?;
+        Ok(())
+    }
+
+    /// Sends a command to the specified host telling it to terminate a capability provider. The success of this
+    /// command indicates a successful publication, and not necessarily a successful remote provider termination.
+    /// Use [stop_provider_and_wait](#method.stop_provider_and_wait) to confirm the provider was removed
+    pub fn stop_provider_on_host(
+        &self,
+        provider_ref: &str,
+        binding_name: &str,
+        host_id: &str,
+    ) -> Result<()> {
+        let msg = TerminateProviderCommand::new(provider_ref, binding_name);
+        self.nc.publish(
+            &self.gen_terminate_provider_subject(host_id),
+            &serde_json::to_vec(&msg)?,
+        )?;
+        let _ = self.nc.flush();
+        Ok(())
+    }
+
+    /// Sends a command to the specified host telling it to terminate a capability provider, then waits for
+    /// the host to publish the matching `ProviderRemoved` event. The event identifies the provider by
+    /// capability ID rather than OCI reference, so the provider's capability ID must be supplied to tell
+    /// its removal apart from that of other providers sharing the binding name. Returns
+    /// `LatticeError::Timeout` if no such event is observed within the given period
+    pub fn stop_provider_and_wait(
+        &self,
+        provider_ref: &str,
+        capid: &str,
+        binding_name: &str,
+        host_id: &str,
+        timeout: Duration,
+    ) -> Result<()> {
+        let sub = self.nc.subscribe(&self.gen_subject(EVENTS))?;
+        self.stop_provider_on_host(provider_ref, binding_name, host_id)?;
+        wait_for_event(&sub, timeout, |e| match e {
+            BusEvent::ProviderRemoved {
+                capid: removed,
+                instance_name,
+                host,
+            } => removed == capid && instance_name == binding_name && host == host_id,
+            _ => false,
+        })?;
+        Ok(())
     }
 }
 
-fn get_connection(host: &str, credsfile: Option<PathBuf>) -> nats::Connection {
-    let mut opts = if let Some(creds) = credsfile {
-        nats::Options::with_credentials(creds)
+fn candidate_eligibility(
+    constraints: &ConstraintSet,
+    labels: &HashMap<String, String>,
+    eligible_reason: &str,
+) -> (bool, String) {
+    let violations = constraints.violations(labels);
+    if violations.is_empty() {
+        (true, eligible_reason.to_string())
     } else {
-        nats::Options::new()
-    };
-    opts = opts.with_name("waSCC Lattice");
-    opts.connect(host).unwrap()
+        let violated: Vec<String> = violations.iter().map(|c| format!("`{}`", c)).collect();
+        (false, format!("does not satisfy {}", violated.join(", ")))
+    }
+}
+
+/// Determines whether a host is eligible in a placement plan built from an auction. The labels are absent
+/// if the host did not reply to the host probe, in which case a bid is trusted only if the hosts evaluated
+/// every constraint themselves, as the auction would
+fn bid_eligibility(
+    constraints: &ConstraintSet,
+    bid: bool,
+    labels: Option<&HashMap<String, String>>,
+) -> (bool, String) {
+    match labels {
+        _ if !bid => (false, "did not bid".to_string()),
+        Some(labels) => candidate_eligibility(constraints, labels, "bid"),
+        None if constraints.requires_client_evaluation() => (
+            false,
+            "bid, but its labels could not be verified".to_string(),
+        ),
+        None => (true, "bid".to_string()),
+    }
+}
+
+/// Determines whether a lattice event reports the given actor starting (`Some(true)`) or stopping
+/// (`Some(false)`) on the given host
+fn actor_lifecycle(event: &BusEvent, actor_key: &str, host_id: &str) -> Option<bool> {
+    match event {
+        BusEvent::ActorStarted { actor, host } if actor == actor_key && host == host_id => {
+            Some(true)
+        }
+        BusEvent::ActorStopped { actor, host } if actor == actor_key && host == host_id => {
+            Some(false)
+        }
+        _ => None,
+    }
+}
+
+/// Indicates whether the given actor identifier is a public key, as used in lattice events and
+/// inventory, rather than an OCI reference
+pub fn is_actor_key(id: &str) -> bool {
+    id.len() == 56 && id.starts_with('M') && id.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Waits on an events subscription for the first event satisfying the predicate, skipping any
+/// messages that cannot be decoded. Returns `LatticeError::Timeout` if no matching event arrives
+/// before the timeout period elapses
+fn wait_for_event<F>(sub: &nats::Subscription, timeout: Duration, predicate: F) -> Result<BusEvent>
+where
+    F: Fn(&BusEvent) -> bool,
+{
+    let deadline = Instant::now() + timeout;
+    loop {
+        let remaining = deadline
+            .checked_duration_since(Instant::now())
+            .ok_or(LatticeError::Timeout)?;
+        let msg = sub.next_timeout(remaining)?;
+        if let Ok(event) = decode_event(&msg.data) {
+            if predicate(&event) {
+                return Ok(event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACTOR: &str = "MB2ZQB6ROOMAYBO4ZCTFYWN7YIVBWA3MTKZYAQKJMTIHE2ELLRW2E3ZW";
+    const OTHER_ACTOR: &str = "MCUCZ7KMLQBRRWAREIBQKTJ64MMQ5YKEGTCRGPPV47N4R72W2SU3EYMU";
+
+    fn started(actor: &str, host: &str) -> BusEvent {
+        BusEvent::ActorStarted {
+            actor: actor.to_string(),
+            host: host.to_string(),
+        }
+    }
+
+    fn stopped(actor: &str, host: &str) -> BusEvent {
+        BusEvent::ActorStopped {
+            actor: actor.to_string(),
+            host: host.to_string(),
+        }
+    }
+
+    #[test]
+    fn lifecycle_recognizes_the_actor_on_the_host() {
+        assert_eq!(
+            actor_lifecycle(&started(ACTOR, "Nhost"), ACTOR, "Nhost"),
+            Some(true)
+        );
+        assert_eq!(
+            actor_lifecycle(&stopped(ACTOR, "Nhost"), ACTOR, "Nhost"),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn lifecycle_ignores_unrelated_actors_on_the_same_host() {
+        assert_eq!(
+            actor_lifecycle(&started(OTHER_ACTOR, "Nhost"), ACTOR, "Nhost"),
+            None
+        );
+        assert_eq!(
+            actor_lifecycle(&stopped(OTHER_ACTOR, "Nhost"), ACTOR, "Nhost"),
+            None
+        );
+    }
+
+    #[test]
+    fn lifecycle_ignores_the_actor_on_other_hosts() {
+        assert_eq!(
+            actor_lifecycle(&started(ACTOR, "Nother"), ACTOR, "Nhost"),
+            None
+        );
+    }
+
+    fn constraints(exprs: &[&str]) -> ConstraintSet {
+        ConstraintSet::new().parse(exprs).unwrap()
+    }
+
+    #[test]
+    fn unprobed_bidders_are_eligible_when_hosts_evaluate_the_constraints() {
+        assert_eq!(
+            bid_eligibility(&constraints(&["zone=east"]), true, None),
+            (true, "bid".to_string())
+        );
+    }
+
+    #[test]
+    fn unprobed_bidders_are_ineligible_when_the_client_must_evaluate() {
+        let (eligible, reason) = bid_eligibility(&constraints(&["cores>4"]), true, None);
+        assert!(!eligible);
+        assert_eq!(reason, "bid, but its labels could not be verified");
+    }
+
+    #[test]
+    fn probed_bidders_are_checked_against_their_labels() {
+        let labels: HashMap<String, String> = vec![("zone".to_string(), "west".to_string())]
+            .into_iter()
+            .collect();
+        let (eligible, reason) = bid_eligibility(&constraints(&["zone=east"]), true, Some(&labels));
+        assert!(!eligible);
+        assert_eq!(reason, "does not satisfy `zone=east`");
+        assert!(bid_eligibility(&constraints(&["zone=west"]), true, Some(&labels)).0);
+    }
+
+    #[test]
+    fn hosts_that_did_not_bid_are_ineligible() {
+        assert_eq!(
+            bid_eligibility(&constraints(&[]), false, Some(&HashMap::new())),
+            (false, "did not bid".to_string())
+        );
+    }
+
+    #[test]
+    fn actor_keys_are_distinguished_from_oci_references() {
+        assert!(is_actor_key(ACTOR));
+        assert!(!is_actor_key("wascc.azurecr.io/echo:v1"));
+        assert!(!is_actor_key(&ACTOR[1..]));
+    }
 }
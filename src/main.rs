@@ -1,9 +1,16 @@
 extern crate latticeclient;
 
 use std::error::Error;
-use std::{collections::HashMap, path::PathBuf, time::Duration};
+use std::{path::PathBuf, time::Duration};
 
 use crossbeam::unbounded;
+use latticeclient::{
+    health::{HealthMonitor, HealthMonitorConfig},
+    is_actor_key, AuctionOptions, Client, Constraint, ConstraintMode, ConstraintSet, EventFilter,
+    FirstResponderStrategy, InventoryReplyError, LabelPreferenceStrategy, LeastLoadedStrategy,
+    PlacementPlan, PlacementStrategy, RandomStrategy, RoundRobinStrategy, ScaleOptions,
+    ScaleReport, ScaleShortfall, SpreadPolicy,
+};
 use structopt::clap::AppSettings;
 use structopt::StructOpt;
 
@@ -18,7 +25,8 @@ struct Cli {
     #[structopt(flatten)]
     command: CliCommand,
 
-    /// The host IP of the nearest NATS server/leaf node to connect to the lattice
+    /// The host IP of the nearest NATS server/leaf node to connect to the lattice. Multiple seed
+    /// servers can be supplied as a comma-separated list
     #[structopt(
         short,
         long,
@@ -34,10 +42,76 @@ struct Cli {
         long,
         env = "LATTICE_CREDS_FILE",
         hide_env_values = true,
-        parse(from_os_str)
+        parse(from_os_str),
+        conflicts_with_all = &["user", "token", "nkey-seed"]
     )]
     creds: Option<PathBuf>,
 
+    /// User name used to authenticate against NATS (requires a password)
+    #[structopt(
+        long,
+        env = "LATTICE_USER",
+        hide_env_values = true,
+        requires = "password",
+        conflicts_with_all = &["token", "nkey-seed"]
+    )]
+    user: Option<String>,
+
+    /// Password used to authenticate against NATS (requires a user name)
+    #[structopt(
+        long,
+        env = "LATTICE_PASSWORD",
+        hide_env_values = true,
+        requires = "user"
+    )]
+    password: Option<String>,
+
+    /// Token used to authenticate against NATS
+    #[structopt(
+        long,
+        env = "LATTICE_TOKEN",
+        hide_env_values = true,
+        conflicts_with = "nkey-seed"
+    )]
+    token: Option<String>,
+
+    /// Seed (private key) of the user nkey used to authenticate against NATS
+    #[structopt(long = "nkey-seed", env = "LATTICE_NKEY_SEED", hide_env_values = true)]
+    nkey_seed: Option<String>,
+
+    /// Certificate file used for TLS client authentication (requires a key)
+    #[structopt(
+        long = "tls-cert",
+        env = "LATTICE_TLS_CERT",
+        hide_env_values = true,
+        parse(from_os_str),
+        requires = "tls-key"
+    )]
+    tls_cert: Option<PathBuf>,
+
+    /// Private key file used for TLS client authentication
+    #[structopt(
+        long = "tls-key",
+        env = "LATTICE_TLS_KEY",
+        hide_env_values = true,
+        parse(from_os_str),
+        requires = "tls-cert"
+    )]
+    tls_key: Option<PathBuf>,
+
+    /// Root certificate file used to verify the NATS server's certificate
+    #[structopt(
+        long = "tls-ca",
+        env = "LATTICE_TLS_CA",
+        hide_env_values = true,
+        parse(from_os_str)
+    )]
+    tls_ca: Option<PathBuf>,
+
+    /// Name of the connection as it appears to the NATS server
+    #[structopt(long = "connection-name", default_value = "latticectl")]
+    connection_name: String,
+
     /// Lattice invocation / request timeout period, in milliseconds
     #[structopt(
         short = "t",
@@ -70,128 +144,726 @@ enum CliCommand {
         entity_type: String,
     },
     #[structopt(name = "watch")]
-    /// Watch events on the lattice
-    Watch,
+    /// Watch events on the lattice. Filters are applied by latticectl as events arrive, so all events are
+    /// still received from the lattice
+    Watch {
+        /// Only show events of the given type (e.g. actor_started)
+        #[structopt(long = "type", number_of_values = 1)]
+        event_type: Vec<String>,
+        /// Only show events that occurred on the given host
+        #[structopt(long, number_of_values = 1)]
+        host: Vec<String>,
+        /// Only show events pertaining to the given actor
+        #[structopt(long, number_of_values = 1)]
+        actor: Vec<String>,
+        /// Only show events pertaining to the given capability ID
+        #[structopt(long, number_of_values = 1)]
+        capid: Vec<String>,
+        /// Only show events pertaining to the given provider instance (binding) name
+        #[structopt(long = "instance", number_of_values = 1)]
+        instance_name: Vec<String>,
+    },
     #[structopt(name = "start")]
     /// Hold a lattice auction for a given actor and start it if a suitable host is found
     Start {
         /// An OCI image reference of the actor to be launched
         actor_ref: String,
-        /// Add limiting constraints to filter potential target hosts (in the form of label=value)
-        #[structopt(short = "c", parse(try_from_str = parse_key_val), number_of_values = 1)]
-        constraint: Vec<(String, String)>,
+        /// Add limiting constraints to filter potential target hosts (e.g. label=value, label!=value,
+        /// "label in (a,b)", "label notin (a,b)", exists(label), !exists(label), label>=4)
+        #[structopt(short = "c", number_of_values = 1)]
+        constraint: Vec<Constraint>,
+        /// Wait up to the given number of seconds for the host to confirm the actor started
+        #[structopt(short = "w", long = "wait")]
+        wait: Option<u64>,
+        /// The public key of the actor, used to recognize its start when waiting. Required with --wait
+        /// unless the actor is referenced by its public key
+        #[structopt(short = "k", long = "key")]
+        actor_key: Option<String>,
+        #[structopt(flatten)]
+        placement: PlacementArgs,
+        /// Show which hosts would be candidates and which would win, without launching the actor
+        #[structopt(long = "dry-run")]
+        dry_run: bool,
+        /// Plan the dry run by evaluating the constraints against host labels instead of holding an auction
+        #[structopt(long = "labels-only", requires = "dry-run")]
+        labels_only: bool,
+        #[structopt(flatten)]
+        auction: AuctionArgs,
+    },
+    #[structopt(name = "start-provider")]
+    /// Hold a lattice auction for a given capability provider and start it if a suitable host is found
+    StartProvider {
+        /// An OCI image reference of the provider to be launched
+        provider_ref: String,
+        /// The binding name of the provider instance
+        #[structopt(short = "b", long = "binding", default_value = "default")]
+        binding_name: String,
+        /// Add limiting constraints to filter potential target hosts (e.g. label=value, label!=value,
+        /// "label in (a,b)", "label notin (a,b)", exists(label), !exists(label), label>=4)
+        #[structopt(short = "c", number_of_values = 1)]
+        constraint: Vec<Constraint>,
+        #[structopt(flatten)]
+        placement: PlacementArgs,
+        #[structopt(flatten)]
+        auction: AuctionArgs,
+    },
+    #[structopt(name = "scale")]
+    /// Scale an actor to the given number of replicas, holding an auction for each replica added and
+    /// stopping surplus replicas
+    Scale {
+        /// An OCI image reference of the actor to be scaled
+        actor_ref: String,
+        /// The public key (subject) of the actor, used to count the replicas already running
+        #[structopt(short = "k", long = "key")]
+        actor_key: String,
+        /// The number of replicas that should be running across the lattice
+        #[structopt(short = "r", long = "replicas")]
+        replicas: usize,
+        /// Whether new replicas must (required), should (preferred) or need not (any) be placed on hosts
+        /// that are not already running the actor
+        #[structopt(
+            long = "spread",
+            default_value = "preferred",
+            possible_values = &["any", "preferred", "required"]
+        )]
+        spread: String,
+        /// Add limiting constraints to filter potential target hosts (e.g. label=value, label!=value,
+        /// "label in (a,b)", "label notin (a,b)", exists(label), !exists(label), label>=4)
+        #[structopt(short = "c", number_of_values = 1)]
+        constraint: Vec<Constraint>,
+        #[structopt(flatten)]
+        placement: PlacementArgs,
+        #[structopt(flatten)]
+        auction: AuctionArgs,
     },
     /// Tell a given host to terminate the given actor
     #[structopt(name = "stop")]
-    Stop { actor: String, host_id: String },
+    Stop {
+        actor: String,
+        host_id: String,
+        /// Wait up to the given number of seconds for the host to confirm the actor stopped, then verify
+        /// that the host no longer reports the actor
+        #[structopt(short = "w", long = "wait")]
+        wait: Option<u64>,
+        /// The public key of the actor, used to recognize its termination when waiting. Required with
+        /// --wait unless the actor is referenced by its public key
+        #[structopt(short = "k", long = "key")]
+        actor_key: Option<String>,
+    },
+    /// Tell a given host to terminate the given capability provider
+    #[structopt(name = "stop-provider")]
+    StopProvider {
+        /// An OCI image reference of the provider to be terminated
+        provider_ref: String,
+        /// The host on which the provider is running
+        host_id: String,
+        /// The binding name of the provider instance
+        #[structopt(short = "b", long = "binding", default_value = "default")]
+        binding_name: String,
+        /// The capability ID of the provider, used to recognize its removal when waiting. Looked up from
+        /// the host's inventory if only one of its providers has the binding name
+        #[structopt(long = "capid")]
+        capid: Option<String>,
+        /// Wait up to the given number of seconds for the host to confirm the provider was removed
+        #[structopt(short = "w", long = "wait")]
+        wait: Option<u64>,
+    },
+    /// Monitor the health of all actors in the lattice, publishing health transition events
+    #[structopt(name = "health-monitor")]
+    HealthMonitor {
+        /// The number of seconds between inventory probes
+        #[structopt(long = "interval", default_value = "10")]
+        interval: u64,
+        /// The number of consecutive probes an actor must be missing from before it becomes unhealthy
+        #[structopt(long = "unhealthy-after", default_value = "3")]
+        unhealthy_after: u32,
+        /// The number of consecutive probes an unhealthy actor must be present in before it becomes healthy
+        #[structopt(long = "healthy-after", default_value = "1")]
+        healthy_after: u32,
+    },
+}
+
+#[derive(Debug, Clone, StructOpt)]
+struct PlacementArgs {
+    /// The strategy used to choose a host from among the auction bids. The round-robin rotation only
+    /// spans the auctions of a single invocation, such as the replicas added by `scale`
+    #[structopt(
+        long = "strategy",
+        default_value = "first-responder",
+        possible_values = &["first-responder", "random", "least-loaded", "label-preference", "round-robin"]
+    )]
+    strategy: String,
+    /// Prefer hosts with the given label when using the label-preference strategy (in the form of label=value)
+    #[structopt(long = "prefer", parse(try_from_str = parse_key_val), number_of_values = 1)]
+    prefer: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, StructOpt)]
+struct AuctionArgs {
+    /// The maximum number of milliseconds to wait for auction bids
+    #[structopt(long = "auction-window", default_value = "5000")]
+    auction_window: u64,
+    /// Close the auction as soon as the given number of bids have been received
+    #[structopt(long = "max-bids", parse(try_from_str = parse_positive))]
+    max_bids: Option<usize>,
+    /// Close the auction as soon as the first bid is received
+    #[structopt(long = "first-responder")]
+    first_responder: bool,
+    /// Close the auction as soon as every host currently in the lattice has bid
+    #[structopt(long = "all-hosts")]
+    all_hosts: bool,
+    /// Send every constraint to hosts as an expression, in addition to the label=value constraints
+    /// understood by all hosts
+    #[structopt(long = "extended-constraints")]
+    extended_constraints: bool,
 }
 
 fn main() {
     let args = Cli::from_args();
-    let cmd = args.command;
-
-    std::process::exit(
-        match handle_command(
-            cmd,
-            args.url,
-            args.json,
-            args.creds,
-            args.namespace,
-            Duration::from_millis(args.call_timeout),
-        ) {
-            Ok(_) => 0,
-            Err(e) => {
-                eprintln!("Latticectl Error: {}", e);
-                1
-            }
-        },
-    )
+    // Surface the warnings logged by long-running commands such as health-monitor, which would
+    // otherwise fail silently. RUST_LOG overrides the default level
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
+    std::process::exit(match handle_command(&args) {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("Latticectl Error: {}", e);
+            1
+        }
+    })
 }
 
-fn handle_command(
-    cmd: CliCommand,
-    url: String,
-    json: bool,
-    creds: Option<PathBuf>,
-    namespace: Option<String>,
-    timeout: Duration,
-) -> Result<(), Box<dyn ::std::error::Error>> {
-    match cmd {
-        CliCommand::List { entity_type } => {
-            list_entities(&entity_type, &url, creds, timeout, json, namespace)
+fn handle_command(args: &Cli) -> Result<(), Box<dyn ::std::error::Error>> {
+    let client = connect(args)?;
+    let json = args.json;
+    match args.command.clone() {
+        CliCommand::List { entity_type } => list_entities(&client, &entity_type, json),
+        CliCommand::Watch {
+            event_type,
+            host,
+            actor,
+            capid,
+            instance_name,
+        } => {
+            let mut filter = EventFilter::new();
+            for t in event_type {
+                filter = filter.event_type(&t);
+            }
+            for h in host {
+                filter = filter.host(&h);
+            }
+            for a in actor {
+                filter = filter.actor(&a);
+            }
+            for c in capid {
+                filter = filter.capid(&c);
+            }
+            for i in instance_name {
+                filter = filter.instance_name(&i);
+            }
+            watch_events(&client, json, filter)
         }
-        CliCommand::Watch => watch_events(&url, creds, timeout, json, namespace),
         CliCommand::Start {
             actor_ref,
             constraint,
-        } => start_actor(&url, creds, timeout, json, namespace, actor_ref, constraint),
-        CliCommand::Stop { actor, host_id } => {
-            stop_actor(&url, creds, timeout, json, namespace, actor, host_id)
+            wait,
+            actor_key,
+            placement,
+            dry_run,
+            labels_only,
+            auction,
+        } => {
+            let wait = match wait {
+                Some(secs) => Some((
+                    Duration::from_secs(secs),
+                    resolve_key(&actor_ref, actor_key)?,
+                )),
+                None => None,
+            };
+            let options = auction_options(&client, &auction)?;
+            let constraints = constraint_set(constraint, &auction);
+            let mut strategy = placement_strategy(&placement);
+            if dry_run {
+                let plan = if labels_only {
+                    client.plan_actor_placement_from_labels(
+                        &actor_ref,
+                        &constraints,
+                        strategy.as_mut(),
+                    )?
+                } else {
+                    client.plan_actor_placement(
+                        &actor_ref,
+                        &constraints,
+                        &options,
+                        strategy.as_mut(),
+                    )?
+                };
+                return render_plan(&plan, json);
+            }
+            start_actor(
+                &client,
+                json,
+                actor_ref,
+                &constraints,
+                wait,
+                &options,
+                strategy.as_mut(),
+            )
+        }
+        CliCommand::StartProvider {
+            provider_ref,
+            binding_name,
+            constraint,
+            placement,
+            auction,
+        } => {
+            let options = auction_options(&client, &auction)?;
+            let constraints = constraint_set(constraint, &auction);
+            let mut strategy = placement_strategy(&placement);
+            start_provider(
+                &client,
+                json,
+                provider_ref,
+                binding_name,
+                &constraints,
+                &options,
+                strategy.as_mut(),
+            )
+        }
+        CliCommand::Scale {
+            actor_ref,
+            actor_key,
+            replicas,
+            spread,
+            constraint,
+            placement,
+            auction,
+        } => {
+            let constraints = constraint_set(constraint, &auction);
+            let spread = match spread.as_str() {
+                "any" => SpreadPolicy::Any,
+                "required" => SpreadPolicy::Required,
+                _ => SpreadPolicy::Preferred,
+            };
+            let options = ScaleOptions::new()
+                .spread(spread)
+                .auction(auction_options(&client, &auction)?);
+            let mut strategy = placement_strategy(&placement);
+            let report = client.scale_actor_with(
+                &actor_ref,
+                &actor_key,
+                replicas,
+                &constraints,
+                &options,
+                strategy.as_mut(),
+            )?;
+            render_scale_report(&report, json)
         }
+        CliCommand::Stop {
+            actor,
+            host_id,
+            wait,
+            actor_key,
+        } => stop_actor(&client, json, actor, host_id, wait, actor_key),
+        CliCommand::StopProvider {
+            provider_ref,
+            host_id,
+            binding_name,
+            capid,
+            wait,
+        } => stop_provider(
+            &client,
+            json,
+            provider_ref,
+            binding_name,
+            host_id,
+            capid,
+            wait,
+        ),
+        CliCommand::HealthMonitor {
+            interval,
+            unhealthy_after,
+            healthy_after,
+        } => monitor_health(
+            client,
+            json,
+            HealthMonitorConfig {
+                probe_interval: Duration::from_secs(interval),
+                unhealthy_threshold: unhealthy_after,
+                healthy_threshold: healthy_after,
+            },
+        ),
+    }
+}
+
+fn connect(args: &Cli) -> Result<Client, Box<dyn ::std::error::Error>> {
+    let mut builder = Client::builder()
+        .servers(&args.url.split(',').map(str::trim).collect::<Vec<_>>())
+        .call_timeout(Duration::from_millis(args.call_timeout))
+        .connection_name(&args.connection_name)
+        .disconnect_callback(|| eprintln!("Lost connection to the lattice"))
+        .reconnect_callback(|| eprintln!("Re-established connection to the lattice"));
+    if let Some(ref creds) = args.creds {
+        builder = builder.credentials(creds);
+    }
+    if let (Some(user), Some(password)) = (&args.user, &args.password) {
+        builder = builder.user_password(user, password);
+    }
+    if let Some(ref token) = args.token {
+        builder = builder.token(token);
+    }
+    if let Some(ref seed) = args.nkey_seed {
+        builder = builder.nkey_seed(seed);
+    }
+    if let (Some(cert), Some(key)) = (&args.tls_cert, &args.tls_key) {
+        builder = builder.client_cert(cert, key);
+    }
+    if let Some(ref ca) = args.tls_ca {
+        builder = builder.root_certificate(ca);
+    }
+    if let Some(ref ns) = args.namespace {
+        builder = builder.namespace(ns);
+    }
+    Ok(builder.build()?)
+}
+
+fn auction_options(
+    client: &Client,
+    args: &AuctionArgs,
+) -> Result<AuctionOptions, Box<dyn ::std::error::Error>> {
+    let mut options = AuctionOptions::new().window(Duration::from_millis(args.auction_window));
+    if let Some(max) = args.max_bids {
+        options = options.max_bids(max);
+    }
+    if args.first_responder {
+        options = options.first_responder();
+    }
+    if args.all_hosts {
+        options = options.known_hosts(client.get_hosts()?.items.len());
+    }
+    Ok(options)
+}
+
+fn constraint_set(constraints: Vec<Constraint>, args: &AuctionArgs) -> ConstraintSet {
+    let mode = if args.extended_constraints {
+        ConstraintMode::Extended
+    } else {
+        ConstraintMode::Legacy
+    };
+    constraints
+        .into_iter()
+        .collect::<ConstraintSet>()
+        .mode(mode)
+}
+
+fn placement_strategy(args: &PlacementArgs) -> Box<dyn PlacementStrategy> {
+    match args.strategy.as_str() {
+        "random" => Box::new(RandomStrategy),
+        "least-loaded" => Box::new(LeastLoadedStrategy),
+        "label-preference" => Box::new(
+            args.prefer
+                .iter()
+                .fold(LabelPreferenceStrategy::new(), |s, (label, value)| {
+                    s.prefer(label, value, 1)
+                }),
+        ),
+        "round-robin" => Box::new(RoundRobinStrategy::new()),
+        _ => Box::new(FirstResponderStrategy),
     }
 }
 
 fn start_actor(
-    url: &str,
-    creds: Option<PathBuf>,
-    timeout: Duration,
+    client: &Client,
     json: bool,
-    namespace: Option<String>,
     actor: String,
-    constraints: Vec<(String, String)>,
+    constraints: &ConstraintSet,
+    wait: Option<(Duration, String)>,
+    options: &AuctionOptions,
+    strategy: &mut dyn PlacementStrategy,
 ) -> Result<(), Box<dyn ::std::error::Error>> {
-    let client = latticeclient::Client::new(url, creds, timeout, namespace);
-    let candidates =
-        client.perform_actor_launch_auction(&actor, constraints_to_hashmap(constraints))?;
-    if candidates.len() > 0 {
-        let ack = client.launch_actor_on_host(&actor, &candidates[0].host_id)?;
-        if ack.actor_id != actor || ack.host != candidates[0].host_id {
-            return Err(format!("Received unexpected acknowledgement: {:?}", ack).into());
-        }
-        if json {
-            println!("{}", serde_json::to_string(&ack)?);
-        } else {
-            println!(
-                "Host {} acknowledged request to launch actor {}.",
-                ack.host, ack.actor_id
-            );
+    let candidates: Vec<String> = client
+        .perform_actor_launch_auction_with(&actor, constraints, options)?
+        .into_iter()
+        .map(|c| c.host_id)
+        .collect();
+    let placement = strategy.select(client, &candidates)?;
+    if !json {
+        println!(
+            "Selected host {} ({}).",
+            placement.host_id, placement.reason
+        );
+    }
+    let ack = match wait {
+        Some((timeout, ref key)) => {
+            client.launch_actor_and_wait(&actor, key, &placement.host_id, timeout)?
         }
+        None => client.launch_actor_on_host(&actor, &placement.host_id)?,
+    };
+    if json {
+        println!("{}", serde_json::to_string(&ack)?);
+    } else if wait.is_some() {
+        println!("Host {} started actor {}.", ack.host, ack.actor_id);
+    } else {
+        println!(
+            "Host {} acknowledged request to launch actor {}.",
+            ack.host, ack.actor_id
+        );
+    }
+    Ok(())
+}
+
+/// Determines the public key used to recognize an actor in lattice events, which is either supplied
+/// explicitly or is the actor reference itself
+fn resolve_key(
+    actor: &str,
+    actor_key: Option<String>,
+) -> Result<String, Box<dyn ::std::error::Error>> {
+    match actor_key {
+        Some(key) => Ok(key),
+        None if is_actor_key(actor) => Ok(actor.to_string()),
+        None => Err(format!(
+            "{} is not an actor public key, use --key to supply the actor's public key",
+            actor
+        )
+        .into()),
+    }
+}
+
+fn render_plan(plan: &PlacementPlan, json: bool) -> Result<(), Box<dyn ::std::error::Error>> {
+    if json {
+        println!("{}", serde_json::to_string(plan)?);
+        return Ok(());
+    }
+    println!("Placement plan for actor {}:", plan.actor_id);
+    for candidate in &plan.candidates {
+        println!(
+            "\t[{}] {} - {}",
+            if candidate.eligible { "x" } else { " " },
+            candidate.host_id,
+            candidate.reason
+        );
+    }
+    match plan.winner {
+        Some(ref winner) => println!(
+            "Would launch on host {} ({}). No launch command was sent.",
+            winner.host_id, winner.reason
+        ),
+        None => println!("No eligible hosts, the actor would not be launched."),
+    }
+    Ok(())
+}
+
+fn start_provider(
+    client: &Client,
+    json: bool,
+    provider_ref: String,
+    binding_name: String,
+    constraints: &ConstraintSet,
+    options: &AuctionOptions,
+    strategy: &mut dyn PlacementStrategy,
+) -> Result<(), Box<dyn ::std::error::Error>> {
+    let candidates: Vec<String> = client
+        .perform_provider_launch_auction_with(&provider_ref, &binding_name, constraints, options)?
+        .into_iter()
+        .map(|c| c.host_id)
+        .collect();
+    let placement = strategy.select(client, &candidates)?;
+    if !json {
+        println!(
+            "Selected host {} ({}).",
+            placement.host_id, placement.reason
+        );
+    }
+    let ack = client.launch_provider_on_host(&provider_ref, &placement.host_id, &binding_name)?;
+    if json {
+        println!("{}", serde_json::to_string(&ack)?);
     } else {
-        println!("Did not receive a response to the actor schedule auction.");
+        println!(
+            "Host {} acknowledged request to launch provider {} ({}).",
+            ack.host, ack.provider_ref, binding_name
+        );
+    }
+    Ok(())
+}
+
+fn render_scale_report(
+    report: &ScaleReport,
+    json: bool,
+) -> Result<(), Box<dyn ::std::error::Error>> {
+    if json {
+        println!("{}", serde_json::to_string(report)?);
+        return Ok(());
+    }
+    for host in &report.launched {
+        println!(
+            "Requested launch of actor {} on host {}.",
+            report.actor, host
+        );
+    }
+    for host in &report.stopped {
+        println!(
+            "Requested termination of actor {} on host {}.",
+            report.actor, host
+        );
+    }
+    if report.is_complete() {
+        println!(
+            "Actor {} scaled from {} to {} replicas.",
+            report.actor,
+            report.previous.len(),
+            report.replicas()
+        );
+    } else {
+        let reason = match &report.shortfall {
+            Some(ScaleShortfall::NoBids) => "no hosts bid to place the next replica".to_string(),
+            Some(ScaleShortfall::NoVacantHosts) => {
+                "every bidding host already runs the actor".to_string()
+            }
+            Some(ScaleShortfall::Failed {
+                host_id: Some(host_id),
+                error,
+            }) => format!("the request to host {} failed: {}", host_id, error),
+            Some(ScaleShortfall::Failed {
+                host_id: None,
+                error,
+            }) => format!("the auction failed: {}", error),
+            None => "no replicas were left to stop".to_string(),
+        };
+        eprintln!(
+            "Warning: actor {} scaled to {} of {} requested replicas; {}.",
+            report.actor,
+            report.replicas(),
+            report.target,
+            reason
+        );
     }
     Ok(())
 }
 
 fn stop_actor(
-    url: &str,
-    creds: Option<PathBuf>,
-    timeout: Duration,
+    client: &Client,
     _json: bool,
-    namespace: Option<String>,
     actor: String,
     host_id: String,
+    wait: Option<u64>,
+    actor_key: Option<String>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let client = latticeclient::Client::new(url, creds, timeout, namespace);
-    client.stop_actor_on_host(&actor, &host_id)?;
-    println!("Termination command sent.");
+    let secs = match wait {
+        Some(secs) => secs,
+        None => {
+            client.stop_actor_on_host(&actor, &host_id)?;
+            println!("Termination command sent.");
+            return Ok(());
+        }
+    };
+    let key = resolve_key(&actor, actor_key)?;
+    let confirmed =
+        match client.stop_actor_and_wait(&actor, &key, &host_id, Duration::from_secs(secs)) {
+            Ok(_) => true,
+            Err(latticeclient::LatticeError::Timeout) => false,
+            Err(e) => return Err(e.into()),
+        };
+    match client.get_actors()?.into_result()?.get(&host_id) {
+        Some(actors) if actors.iter().any(|a| a.subject == key) => {
+            Err(format!("Actor {} is still running on host {}", actor, host_id).into())
+        }
+        None if !confirmed => Err(format!(
+            "Host {} neither confirmed that actor {} stopped nor answered the inventory probe",
+            host_id, actor
+        )
+        .into()),
+        _ => {
+            println!("Host {} stopped actor {}.", host_id, actor);
+            Ok(())
+        }
+    }
+}
+
+fn stop_provider(
+    client: &Client,
+    _json: bool,
+    provider_ref: String,
+    binding_name: String,
+    host_id: String,
+    capid: Option<String>,
+    wait: Option<u64>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match wait {
+        Some(secs) => {
+            let capid = match capid {
+                Some(capid) => capid,
+                None => provider_capid(client, &host_id, &binding_name)?,
+            };
+            client.stop_provider_and_wait(
+                &provider_ref,
+                &capid,
+                &binding_name,
+                &host_id,
+                Duration::from_secs(secs),
+            )?;
+            println!(
+                "Host {} removed provider {} ({}).",
+                host_id, provider_ref, binding_name
+            );
+        }
+        None => {
+            client.stop_provider_on_host(&provider_ref, &binding_name, &host_id)?;
+            println!("Termination command sent.");
+        }
+    }
     Ok(())
 }
 
+/// Finds the capability ID of the provider with the given binding name on the host, which must be unique
+fn provider_capid(
+    client: &Client,
+    host_id: &str,
+    binding_name: &str,
+) -> Result<String, Box<dyn ::std::error::Error>> {
+    let capids: Vec<String> = client
+        .get_capabilities()?
+        .into_result()?
+        .remove(host_id)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|c| c.binding_name == binding_name)
+        .map(|c| c.descriptor.id)
+        .collect();
+    match capids.as_slice() {
+        [capid] => Ok(capid.to_string()),
+        [] => Err(format!(
+            "Host {} is not running a provider with binding name {}",
+            host_id, binding_name
+        )
+        .into()),
+        _ => Err(format!(
+            "Host {} is running several providers with binding name {} ({}), use --capid to choose one",
+            host_id,
+            binding_name,
+            capids.join(", ")
+        )
+        .into()),
+    }
+}
+
 fn watch_events(
-    url: &str,
-    creds: Option<PathBuf>,
-    timeout: Duration,
+    client: &Client,
     json: bool,
-    namespace: Option<String>,
+    filter: EventFilter,
 ) -> Result<(), Box<dyn ::std::error::Error>> {
     if !json {
         println!("Watching lattice events, Ctrl+C to abort...");
     }
-    let client = latticeclient::Client::new(url, creds, timeout, namespace);
     let (s, r) = unbounded();
-    client.watch_events(s)?;
+    let _sub = client.watch_events_filtered(filter, s)?;
     loop {
-        let be = r.recv()?;
+        let be = match r.recv()? {
+            Ok(be) => be,
+            Err(e) => {
+                eprintln!("Warning: {} (payload: {})", e, e.payload());
+                continue;
+            }
+        };
         if json {
             let raw = serde_json::to_string(&be)?;
             println!("{}", raw);
@@ -201,122 +873,194 @@ fn watch_events(
     }
 }
 
+fn monitor_health(
+    client: Client,
+    json: bool,
+    config: HealthMonitorConfig,
+) -> Result<(), Box<dyn ::std::error::Error>> {
+    if !json {
+        println!("Monitoring actor health, Ctrl+C to abort...");
+    }
+    let mut monitor = HealthMonitor::new(client, config);
+    monitor.run(|be| {
+        if json {
+            if let Ok(raw) = serde_json::to_string(be) {
+                println!("{}", raw);
+            }
+        } else {
+            println!("{}", be);
+        }
+    })?;
+    Ok(())
+}
+
 fn list_entities(
+    client: &Client,
     entity_type: &str,
-    url: &str,
-    creds: Option<PathBuf>,
-    timeout: Duration,
     json: bool,
-    namespace: Option<String>,
 ) -> Result<(), Box<dyn ::std::error::Error>> {
-    let client = latticeclient::Client::new(url, creds, timeout, namespace);
     match entity_type.to_lowercase().trim() {
-        "hosts" => render_hosts(&client, json),
-        "actors" => render_actors(&client, json),
-        "bindings" => render_bindings(&client, json),
-        "capabilities" | "caps" => render_capabilities(&client, json),
+        "hosts" => render_hosts(client, json),
+        "actors" => render_actors(client, json),
+        "bindings" => render_bindings(client, json),
+        "capabilities" | "caps" => render_capabilities(client, json),
         _ => Err(
             "Unknown entity type. Valid types are: hosts, actors, capabilities, bindings".into(),
         ),
     }
 }
 
-fn render_actors(
-    client: &latticeclient::Client,
-    json: bool,
-) -> Result<(), Box<dyn ::std::error::Error>> {
-    let actors = client.get_actors()?;
+fn render_actors(client: &Client, json: bool) -> Result<(), Box<dyn ::std::error::Error>> {
     if json {
-        println!("{}", serde_json::to_string(&actors)?);
-    } else {
-        for (host, actors) in actors {
-            println!("\nHost {}:", host);
-            for actor in actors {
-                let md = actor.metadata.clone().unwrap();
-                println!(
-                    "\t{} - {}  v{} ({})",
-                    actor.subject,
-                    actor.name(),
-                    md.ver.unwrap_or("???".into()),
-                    md.rev.unwrap_or(0)
-                );
+        let report = client.get_actors()?;
+        println!("{}", serde_json::to_string(&report.items)?);
+        print_reply_errors(&report.errors);
+        return Ok(());
+    }
+    let mut errors = vec![];
+    for reply in client.stream_actors()? {
+        let (host, actors) = match reply {
+            Ok(r) => r,
+            Err(e) => {
+                errors.push(e);
+                continue;
             }
+        };
+        println!("\nHost {}:", host);
+        for actor in actors {
+            let md = actor.metadata.clone().unwrap();
+            println!(
+                "\t{} - {}  v{} ({})",
+                actor.subject,
+                actor.name(),
+                md.ver.unwrap_or("???".into()),
+                md.rev.unwrap_or(0)
+            );
         }
     }
+    print_reply_errors(&errors);
     Ok(())
 }
 
-fn render_hosts(
-    client: &latticeclient::Client,
-    json: bool,
-) -> Result<(), Box<dyn ::std::error::Error>> {
-    let hosts = client.get_hosts()?;
+fn render_hosts(client: &Client, json: bool) -> Result<(), Box<dyn ::std::error::Error>> {
     if json {
-        println!("{}", serde_json::to_string(&hosts)?);
-    } else {
-        for host in hosts {
-            println!(
-                "[{}] Uptime {}s, Labels: {}",
-                host.id,
-                host.uptime_ms / 1000,
-                host.labels
-                    .keys()
-                    .map(|k| k.to_string())
-                    .collect::<Vec<_>>()
-                    .join(",")
-            );
-        }
+        let report = client.get_hosts()?;
+        println!("{}", serde_json::to_string(&report.items)?);
+        print_reply_errors(&report.errors);
+        return Ok(());
+    }
+    let mut errors = vec![];
+    for reply in client.stream_hosts()? {
+        let host = match reply {
+            Ok(h) => h,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
+        println!(
+            "[{}] Uptime {}s, Labels: {}",
+            host.id,
+            host.uptime_ms / 1000,
+            host.labels
+                .keys()
+                .map(|k| k.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        );
     }
+    print_reply_errors(&errors);
     Ok(())
 }
 
-fn render_capabilities(
-    client: &latticeclient::Client,
-    json: bool,
-) -> Result<(), Box<dyn ::std::error::Error>> {
-    let caps = client.get_capabilities()?;
+fn render_capabilities(client: &Client, json: bool) -> Result<(), Box<dyn ::std::error::Error>> {
     if json {
-        println!("{}", serde_json::to_string(&caps)?);
-    } else {
-        for (host, caps) in caps {
-            println!("{}", host);
-            for cap in caps {
-                println!(
-                    "\t{},{} - Total Operations {}",
-                    cap.descriptor.id,
-                    cap.binding_name,
-                    cap.descriptor.supported_operations.len()
-                );
+        let report = client.get_capabilities()?;
+        println!("{}", serde_json::to_string(&report.items)?);
+        print_reply_errors(&report.errors);
+        return Ok(());
+    }
+    let mut errors = vec![];
+    for reply in client.stream_capabilities()? {
+        let (host, caps) = match reply {
+            Ok(r) => r,
+            Err(e) => {
+                errors.push(e);
+                continue;
             }
+        };
+        println!("{}", host);
+        for cap in caps {
+            println!(
+                "\t{},{} - Total Operations {}",
+                cap.descriptor.id,
+                cap.binding_name,
+                cap.descriptor.supported_operations.len()
+            );
         }
     }
+    print_reply_errors(&errors);
     Ok(())
 }
 
-fn render_bindings(
-    client: &latticeclient::Client,
-    json: bool,
-) -> Result<(), Box<dyn ::std::error::Error>> {
-    let bindings = client.get_bindings()?;
+fn render_bindings(client: &Client, json: bool) -> Result<(), Box<dyn ::std::error::Error>> {
     if json {
-        println!("{}", serde_json::to_string(&bindings)?);
-    } else {
-        for (host, bindings) in bindings {
-            println!("Host {}", host);
-            for binding in bindings {
-                println!(
-                    "\t{} -> {},{} - {} values",
-                    binding.actor,
-                    binding.capability_id,
-                    binding.binding_name,
-                    binding.configuration.len()
-                );
+        let report = client.get_bindings()?;
+        println!("{}", serde_json::to_string(&report.items)?);
+        print_reply_errors(&report.errors);
+        return Ok(());
+    }
+    let mut errors = vec![];
+    for reply in client.stream_bindings()? {
+        let (host, bindings) = match reply {
+            Ok(r) => r,
+            Err(e) => {
+                errors.push(e);
+                continue;
             }
+        };
+        println!("Host {}", host);
+        for binding in bindings {
+            println!(
+                "\t{} -> {},{} - {} values",
+                binding.actor,
+                binding.capability_id,
+                binding.binding_name,
+                binding.configuration.len()
+            );
         }
     }
+    print_reply_errors(&errors);
     Ok(())
 }
 
+fn print_reply_errors(errors: &[InventoryReplyError]) {
+    if errors.is_empty() {
+        return;
+    }
+    eprintln!(
+        "\nWarning: {} inventory {} could not be decoded and {} omitted:",
+        errors.len(),
+        if errors.len() == 1 {
+            "reply"
+        } else {
+            "replies"
+        },
+        if errors.len() == 1 { "was" } else { "were" }
+    );
+    for e in errors {
+        eprintln!("\t{} (payload: {})", e, e.payload());
+    }
+}
+
+/// Parse a count that must be at least one
+fn parse_positive(s: &str) -> Result<usize, Box<dyn Error>> {
+    match s.parse()? {
+        0 => Err("must be at least 1".into()),
+        n => Ok(n),
+    }
+}
+
 /// Parse a single key-value pair
 fn parse_key_val<T, U>(s: &str) -> Result<(T, U), Box<dyn Error>>
 where
@@ -330,11 +1074,3 @@ where
         .ok_or_else(|| format!("invalid KEY=value: no `=` found in `{}`", s))?;
     Ok((s[..pos].parse()?, s[pos + 1..].parse()?))
 }
-
-fn constraints_to_hashmap(input: Vec<(String, String)>) -> HashMap<String, String> {
-    let mut hm = HashMap::new();
-    for (k, v) in input {
-        hm.insert(k.to_owned(), v.to_owned());
-    }
-    hm
-}
@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+
+use rand::seq::SliceRandom;
+
+use crate::{Client, LatticeError, Result};
+
+/// The host chosen by a [PlacementStrategy](trait.PlacementStrategy.html), along with a human-readable
+/// explanation of why it was chosen
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Placement {
+    pub host_id: String,
+    pub reason: String,
+}
+
+/// A host considered while planning a placement, and whether it is eligible to be chosen
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlacementCandidate {
+    pub host_id: String,
+    /// The host's labels, which are empty if the host did not reply to the host probe in time
+    pub labels: HashMap<String, String>,
+    pub eligible: bool,
+    /// Why the host is or is not eligible
+    pub reason: String,
+}
+
+/// A preview of where an actor would be placed, produced without launching it. Every host known
+/// to the lattice is listed as a candidate, along with the winner chosen from among the eligible
+/// candidates (if there are any)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlacementPlan {
+    pub actor_id: String,
+    pub candidates: Vec<PlacementCandidate>,
+    pub winner: Option<Placement>,
+}
+
+/// A strategy for choosing the winner of a launch auction from among the hosts that bid on it.
+/// Candidates are supplied as host IDs in the order in which their bids arrived. If there are no
+/// candidates, strategies return `LatticeError::EmptyAuction`
+pub trait PlacementStrategy {
+    /// Chooses a host from among the given candidates, using the client to query the lattice if the
+    /// strategy requires more information about the candidates
+    fn select(&mut self, client: &Client, candidates: &[String]) -> Result<Placement>;
+}
+
+/// Chooses the first host to bid on the auction. This is the fastest strategy, as it never queries
+/// the lattice
+#[derive(Debug, Clone, Default)]
+pub struct FirstResponderStrategy;
+
+impl PlacementStrategy for FirstResponderStrategy {
+    fn select(&mut self, _client: &Client, candidates: &[String]) -> Result<Placement> {
+        let host_id = candidates.first().ok_or(LatticeError::EmptyAuction)?;
+        Ok(Placement {
+            host_id: host_id.to_string(),
+            reason: "first host to bid".to_string(),
+        })
+    }
+}
+
+/// Chooses a host at random from among the candidates
+#[derive(Debug, Clone, Default)]
+pub struct RandomStrategy;
+
+impl PlacementStrategy for RandomStrategy {
+    fn select(&mut self, _client: &Client, candidates: &[String]) -> Result<Placement> {
+        let host_id = candidates
+            .choose(&mut rand::thread_rng())
+            .ok_or(LatticeError::EmptyAuction)?;
+        Ok(Placement {
+            host_id: host_id.to_string(),
+            reason: format!("chosen at random from {} candidates", candidates.len()),
+        })
+    }
+}
+
+/// Chooses the host running the fewest actors, as reported by `get_actors`. Ties are broken in favor
+/// of the host that bid first
+#[derive(Debug, Clone, Default)]
+pub struct LeastLoadedStrategy;
+
+impl PlacementStrategy for LeastLoadedStrategy {
+    fn select(&mut self, client: &Client, candidates: &[String]) -> Result<Placement> {
+        let counts = client
+            .get_actors()?
+            .items
+            .iter()
+            .map(|(host, actors)| (host.to_string(), actors.len()))
+            .collect();
+        let (host_id, count) =
+            least_loaded(candidates, &counts).ok_or(LatticeError::EmptyAuction)?;
+        Ok(Placement {
+            host_id: host_id.to_string(),
+            reason: format!(
+                "running {} actors, the fewest of {} candidates",
+                count,
+                candidates.len()
+            ),
+        })
+    }
+}
+
+// The candidate running the fewest actors, preferring the earliest bid on ties
+fn least_loaded<'a>(
+    candidates: &'a [String],
+    counts: &HashMap<String, usize>,
+) -> Option<(&'a String, usize)> {
+    candidates
+        .iter()
+        .map(|h| (h, counts.get(h).copied().unwrap_or(0)))
+        .min_by_key(|(_, count)| *count)
+}
+
+/// Scores each candidate by summing the weights of the preferred labels that its host carries, as
+/// reported by `get_hosts`, and chooses the highest scoring host. Ties are broken in favor of the
+/// host that bid first
+#[derive(Debug, Clone, Default)]
+pub struct LabelPreferenceStrategy {
+    preferences: Vec<(String, String, i32)>,
+}
+
+impl LabelPreferenceStrategy {
+    /// Creates a new strategy with no preferences, which chooses the first host to bid
+    pub fn new() -> LabelPreferenceStrategy {
+        LabelPreferenceStrategy::default()
+    }
+
+    /// Adds the given weight to the score of any host whose label has the given value. Negative
+    /// weights can be used to avoid hosts
+    pub fn prefer(mut self, label: &str, value: &str, weight: i32) -> LabelPreferenceStrategy {
+        self.preferences
+            .push((label.to_string(), value.to_string(), weight));
+        self
+    }
+
+    fn score(&self, labels: &HashMap<String, String>) -> i32 {
+        self.preferences
+            .iter()
+            .filter(|(label, value, _)| labels.get(label) == Some(value))
+            .map(|(_, _, weight)| weight)
+            .sum()
+    }
+
+    // The highest scoring candidate, preferring the earliest bid on ties. Candidates without known
+    // labels score as if they carried none
+    fn best<'a>(
+        &self,
+        candidates: &'a [String],
+        labels: &HashMap<String, HashMap<String, String>>,
+    ) -> Option<(&'a String, i32)> {
+        let empty = HashMap::new();
+        let mut best: Option<(&String, i32)> = None;
+        for candidate in candidates {
+            let score = self.score(labels.get(candidate).unwrap_or(&empty));
+            let better = match best {
+                Some((_, best_score)) => score > best_score,
+                None => true,
+            };
+            if better {
+                best = Some((candidate, score));
+            }
+        }
+        best
+    }
+}
+
+impl PlacementStrategy for LabelPreferenceStrategy {
+    fn select(&mut self, client: &Client, candidates: &[String]) -> Result<Placement> {
+        let labels = client
+            .get_hosts()?
+            .items
+            .into_iter()
+            .map(|h| (h.id, h.labels))
+            .collect();
+        let (host_id, score) = self
+            .best(candidates, &labels)
+            .ok_or(LatticeError::EmptyAuction)?;
+        Ok(Placement {
+            host_id: host_id.to_string(),
+            reason: format!(
+                "label preference score {}, the highest of {} candidates",
+                score,
+                candidates.len()
+            ),
+        })
+    }
+}
+
+/// Rotates through the candidates in order of host ID, choosing the next host each time the strategy
+/// is used. The rotation is held by the strategy itself, so it only spans the placements made with the
+/// same instance (such as the replicas added by one call to `scale_actor_with`); a new instance always
+/// starts with the lowest host ID
+#[derive(Debug, Clone, Default)]
+pub struct RoundRobinStrategy {
+    next: usize,
+}
+
+impl RoundRobinStrategy {
+    /// Creates a new strategy that starts its rotation with the lowest host ID
+    pub fn new() -> RoundRobinStrategy {
+        RoundRobinStrategy::default()
+    }
+
+    // Advances the rotation, returning the chosen host and its 1-based position among the candidates
+    fn advance(&mut self, candidates: &[String]) -> Option<(String, usize)> {
+        if candidates.is_empty() {
+            return None;
+        }
+        let mut ordered: Vec<&String> = candidates.iter().collect();
+        ordered.sort();
+        let index = self.next % ordered.len();
+        self.next = self.next.wrapping_add(1);
+        Some((ordered[index].to_string(), index + 1))
+    }
+}
+
+impl PlacementStrategy for RoundRobinStrategy {
+    fn select(&mut self, _client: &Client, candidates: &[String]) -> Result<Placement> {
+        let (host_id, position) = self.advance(candidates).ok_or(LatticeError::EmptyAuction)?;
+        Ok(Placement {
+            host_id,
+            reason: format!(
+                "next in rotation ({} of {} candidates)",
+                position,
+                candidates.len()
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hosts(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn labels(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(l, v)| (l.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn preferences() -> LabelPreferenceStrategy {
+        LabelPreferenceStrategy::new()
+            .prefer("zone", "east", 10)
+            .prefer("gpu", "true", 5)
+            .prefer("tier", "spot", -20)
+    }
+
+    #[test]
+    fn label_scores_sum_matching_weights() {
+        let strategy = preferences();
+        assert_eq!(strategy.score(&labels(&[])), 0);
+        assert_eq!(strategy.score(&labels(&[("zone", "east")])), 10);
+        assert_eq!(strategy.score(&labels(&[("zone", "west")])), 0);
+        assert_eq!(
+            strategy.score(&labels(&[("zone", "east"), ("gpu", "true")])),
+            15
+        );
+        assert_eq!(
+            strategy.score(&labels(&[("zone", "east"), ("tier", "spot")])),
+            -10
+        );
+    }
+
+    #[test]
+    fn highest_label_score_wins() {
+        let known: HashMap<String, HashMap<String, String>> = vec![
+            ("Na".to_string(), labels(&[("tier", "spot")])),
+            ("Nb".to_string(), labels(&[("gpu", "true")])),
+            ("Nc".to_string(), labels(&[("zone", "east")])),
+        ]
+        .into_iter()
+        .collect();
+        let candidates = hosts(&["Na", "Nb", "Nc"]);
+        assert_eq!(
+            preferences().best(&candidates, &known),
+            Some((&candidates[2], 10))
+        );
+    }
+
+    #[test]
+    fn label_score_ties_go_to_the_first_bidder() {
+        let known: HashMap<String, HashMap<String, String>> = vec![
+            ("Na".to_string(), labels(&[("zone", "east")])),
+            ("Nb".to_string(), labels(&[("zone", "east")])),
+        ]
+        .into_iter()
+        .collect();
+        let candidates = hosts(&["Nb", "Na", "Nunknown"]);
+        assert_eq!(
+            preferences().best(&candidates, &known),
+            Some((&candidates[0], 10))
+        );
+        // Without preferences every host ties, so the first bidder wins
+        assert_eq!(
+            LabelPreferenceStrategy::new().best(&candidates, &known),
+            Some((&candidates[0], 0))
+        );
+        assert_eq!(preferences().best(&[], &known), None);
+    }
+
+    #[test]
+    fn least_loaded_prefers_the_first_bidder_on_ties() {
+        let counts: HashMap<String, usize> = vec![
+            ("Na".to_string(), 3),
+            ("Nb".to_string(), 1),
+            ("Nc".to_string(), 1),
+        ]
+        .into_iter()
+        .collect();
+        let candidates = hosts(&["Na", "Nc", "Nb"]);
+        assert_eq!(
+            least_loaded(&candidates, &counts),
+            Some((&candidates[1], 1))
+        );
+
+        // Hosts that report no actors run none
+        let candidates = hosts(&["Na", "Nidle"]);
+        assert_eq!(
+            least_loaded(&candidates, &counts),
+            Some((&candidates[1], 0))
+        );
+    }
+
+    #[test]
+    fn round_robin_rotates_in_host_id_order() {
+        let mut strategy = RoundRobinStrategy::new();
+        let candidates = hosts(&["Nc", "Na", "Nb"]);
+        let chosen: Vec<String> = (0..4)
+            .map(|_| strategy.advance(&candidates).unwrap().0)
+            .collect();
+        assert_eq!(chosen, hosts(&["Na", "Nb", "Nc", "Na"]));
+    }
+
+    #[test]
+    fn round_robin_wraps_when_candidates_change() {
+        let mut strategy = RoundRobinStrategy::new();
+        strategy.advance(&hosts(&["Na", "Nb", "Nc"]));
+        strategy.advance(&hosts(&["Na", "Nb", "Nc"]));
+        assert_eq!(
+            strategy.advance(&hosts(&["Nb", "Na"])),
+            Some(("Na".to_string(), 1))
+        );
+        assert_eq!(strategy.advance(&[]), None);
+    }
+}
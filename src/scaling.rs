@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+
+use crate::AuctionOptions;
+
+/// Controls whether the replicas of a scaled actor are spread across distinct hosts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SpreadPolicy {
+    /// New replicas can be placed on any bidding host, even if it already runs the actor
+    Any,
+    /// New replicas are placed on hosts that do not already run the actor where possible, falling
+    /// back to any bidding host
+    #[default]
+    Preferred,
+    /// New replicas are only placed on hosts that do not already run the actor. Scaling stops short
+    /// of the requested replica count if there are not enough such hosts
+    Required,
+}
+
+impl SpreadPolicy {
+    /// Returns the bidding hosts that a new replica may be placed on, in bid order, given the hosts
+    /// already running a replica
+    pub fn candidates(&self, bids: &[String], occupied: &[String]) -> Vec<String> {
+        let vacant: Vec<String> = bids
+            .iter()
+            .filter(|b| !occupied.contains(b))
+            .cloned()
+            .collect();
+        match self {
+            SpreadPolicy::Any => bids.to_vec(),
+            SpreadPolicy::Preferred if vacant.is_empty() => bids.to_vec(),
+            SpreadPolicy::Preferred | SpreadPolicy::Required => vacant,
+        }
+    }
+}
+
+/// Controls how [scale_actor_with](struct.Client.html#method.scale_actor_with) places new replicas
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScaleOptions {
+    pub(crate) spread: SpreadPolicy,
+    pub(crate) auction: AuctionOptions,
+}
+
+impl ScaleOptions {
+    /// Creates a new set of scaling options with the preferred spread policy and default auctions
+    pub fn new() -> ScaleOptions {
+        ScaleOptions::default()
+    }
+
+    /// Sets whether new replicas are spread across distinct hosts
+    pub fn spread(mut self, spread: SpreadPolicy) -> ScaleOptions {
+        self.spread = spread;
+        self
+    }
+
+    /// Sets how the auction for each new replica collects bids
+    pub fn auction(mut self, auction: AuctionOptions) -> ScaleOptions {
+        self.auction = auction;
+        self
+    }
+}
+
+/// The reason scaling stopped short of the requested replica count
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ScaleShortfall {
+    /// No hosts bid on the auction for a new replica
+    NoBids,
+    /// Hosts bid on the auction for a new replica, but the spread policy excluded all of them
+    NoVacantHosts,
+    /// An auction, launch or stop request failed. The host is absent if the failure occurred before
+    /// a host was chosen
+    Failed {
+        host_id: Option<String>,
+        error: String,
+    },
+}
+
+/// The outcome of scaling an actor. Scaling can stop short of the requested replica count, for example
+/// when no hosts bid on an auction, so the report records what was actually done
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScaleReport {
+    /// The public key of the scaled actor
+    pub actor: String,
+    /// The requested number of replicas
+    pub target: usize,
+    /// The IDs of the hosts running the actor before scaling, one entry per replica
+    pub previous: Vec<String>,
+    /// The IDs of the hosts asked to launch a new replica
+    pub launched: Vec<String>,
+    /// The IDs of the hosts asked to stop a replica
+    pub stopped: Vec<String>,
+    /// Why scaling stopped short of the requested replica count, if it did
+    pub shortfall: Option<ScaleShortfall>,
+}
+
+impl ScaleReport {
+    /// The number of replicas expected to be running once every launch and stop request completes
+    pub fn replicas(&self) -> usize {
+        self.previous.len() + self.launched.len() - self.stopped.len()
+    }
+
+    /// Indicates whether the requested number of replicas was reached
+    pub fn is_complete(&self) -> bool {
+        self.replicas() == self.target
+    }
+}
+
+/// Chooses the host to stop a surplus replica on, given the number of replicas each host runs. The
+/// host running the most replicas is chosen to preserve the spread of the remainder, with ties broken
+/// by the lowest host ID so that the choice does not depend on map iteration order
+pub(crate) fn most_crowded(replicas: &HashMap<String, usize>) -> Option<String> {
+    replicas
+        .iter()
+        .filter(|(_, count)| **count > 0)
+        .max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then_with(|| b.cmp(a)))
+        .map(|(host_id, _)| host_id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACTOR: &str = "MB2ZQB6ROOMAYBO4ZCTFYWN7YIVBWA3MTKZYAQKJMTIHE2ELLRW2E3ZW";
+
+    fn hosts(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn report(
+        previous: &[&str],
+        launched: &[&str],
+        stopped: &[&str],
+        target: usize,
+    ) -> ScaleReport {
+        ScaleReport {
+            actor: ACTOR.to_string(),
+            target,
+            previous: hosts(previous),
+            launched: hosts(launched),
+            stopped: hosts(stopped),
+            shortfall: None,
+        }
+    }
+
+    #[test]
+    fn replicas_account_for_launches_and_stops() {
+        assert_eq!(report(&["a", "b"], &["c"], &[], 3).replicas(), 3);
+        assert_eq!(report(&["a", "a", "b"], &[], &["a"], 2).replicas(), 2);
+        assert_eq!(report(&[], &[], &[], 0).replicas(), 0);
+    }
+
+    #[test]
+    fn complete_only_at_target() {
+        assert!(report(&["a"], &["b"], &[], 2).is_complete());
+        assert!(report(&["a", "b"], &[], &["b"], 1).is_complete());
+        assert!(!report(&["a"], &[], &[], 2).is_complete());
+        assert!(!report(&["a", "b"], &[], &[], 1).is_complete());
+    }
+
+    #[test]
+    fn any_spread_keeps_every_bid() {
+        let candidates = SpreadPolicy::Any.candidates(&hosts(&["a", "b"]), &hosts(&["a"]));
+        assert_eq!(candidates, hosts(&["a", "b"]));
+    }
+
+    #[test]
+    fn preferred_spread_favors_vacant_hosts() {
+        let candidates =
+            SpreadPolicy::Preferred.candidates(&hosts(&["a", "b", "c"]), &hosts(&["a"]));
+        assert_eq!(candidates, hosts(&["b", "c"]));
+    }
+
+    #[test]
+    fn preferred_spread_falls_back_to_occupied_hosts() {
+        let candidates =
+            SpreadPolicy::Preferred.candidates(&hosts(&["a", "b"]), &hosts(&["a", "b"]));
+        assert_eq!(candidates, hosts(&["a", "b"]));
+    }
+
+    #[test]
+    fn required_spread_excludes_occupied_hosts() {
+        let bids = hosts(&["a", "b"]);
+        assert_eq!(
+            SpreadPolicy::Required.candidates(&bids, &hosts(&["a"])),
+            hosts(&["b"])
+        );
+        assert!(SpreadPolicy::Required
+            .candidates(&bids, &hosts(&["a", "b"]))
+            .is_empty());
+    }
+
+    #[test]
+    fn most_crowded_host_is_stopped_first() {
+        let replicas: HashMap<String, usize> = vec![
+            ("a".to_string(), 1),
+            ("b".to_string(), 3),
+            ("c".to_string(), 2),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(most_crowded(&replicas), Some("b".to_string()));
+    }
+
+    #[test]
+    fn ties_are_broken_by_lowest_host_id() {
+        let replicas: HashMap<String, usize> = vec![
+            ("c".to_string(), 2),
+            ("a".to_string(), 2),
+            ("b".to_string(), 2),
+            ("d".to_string(), 0),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(most_crowded(&replicas), Some("a".to_string()));
+        assert_eq!(most_crowded(&HashMap::new()), None);
+    }
+}
@@ -0,0 +1,479 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    thread,
+    time::Duration,
+};
+
+use crossbeam::channel::{tick, unbounded};
+use wascap::prelude::*;
+use wascc_codec::capabilities::CapabilityDescriptor;
+
+use crate::{Binding, BusEvent, Client, HostProfile, HostedCapability, InventoryReport, Result};
+
+/// A point-in-time view of the hosts, actors, capabilities and bindings within the lattice, keyed by
+/// host ID. Lifecycle events only carry identifiers, so entries added by [apply](#method.apply) hold
+/// placeholder details (empty labels, claims containing only the subject, descriptors containing only
+/// the capability ID, empty binding configuration) until the next full load fills them in
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LatticeSnapshot {
+    pub hosts: HashMap<String, HostProfile>,
+    pub actors: HashMap<String, Vec<Claims<Actor>>>,
+    pub capabilities: HashMap<String, Vec<HostedCapability>>,
+    pub bindings: HashMap<String, Vec<Binding>>,
+}
+
+impl LatticeSnapshot {
+    /// Loads a complete snapshot by probing the lattice inventory. Like the inventory probes themselves,
+    /// hosts that do not reply within the client timeout period are not included, and replies that cannot
+    /// be decoded are logged and skipped
+    pub fn load(client: &Client) -> Result<LatticeSnapshot> {
+        LatticeSnapshot::default().reload(client)
+    }
+
+    /// Loads a fresh snapshot by probing the lattice inventory, as [load](#method.load) does. Replies that
+    /// cannot be decoded are logged, and the entries this snapshot holds for the hosts that sent them are
+    /// carried over, since the probe says nothing about those hosts
+    pub fn reload(&self, client: &Client) -> Result<LatticeSnapshot> {
+        let hosts = client.get_hosts()?;
+        let hosts = InventoryReport {
+            items: hosts
+                .items
+                .into_iter()
+                .map(|h| (h.id.to_string(), h))
+                .collect(),
+            errors: hosts.errors,
+        };
+        Ok(LatticeSnapshot {
+            hosts: retain_undecoded(hosts, &self.hosts),
+            actors: retain_undecoded(client.get_actors()?, &self.actors),
+            capabilities: retain_undecoded(client.get_capabilities()?, &self.capabilities),
+            bindings: retain_undecoded(client.get_bindings()?, &self.bindings),
+        })
+    }
+
+    /// Applies a lattice event to the snapshot. Applying the same event more than once has no further effect
+    pub fn apply(&mut self, event: &BusEvent) {
+        match event {
+            BusEvent::HostStarted(host) => self.track_host(host),
+            BusEvent::HostStopped(host) => {
+                self.hosts.remove(host);
+                self.actors.remove(host);
+                self.capabilities.remove(host);
+                self.bindings.remove(host);
+            }
+            BusEvent::ActorStarted { actor, host } => {
+                self.track_host(host);
+                let actors = self.actors.entry(host.to_string()).or_default();
+                if !actors.iter().any(|a| a.subject == *actor) {
+                    actors.push(Claims {
+                        subject: actor.to_string(),
+                        ..Default::default()
+                    });
+                }
+            }
+            BusEvent::ActorStopped { actor, host } => {
+                remove_where(&mut self.actors, host, |a| a.subject == *actor);
+            }
+            BusEvent::ProviderLoaded {
+                capid,
+                instance_name,
+                host,
+            } => {
+                self.track_host(host);
+                let caps = self.capabilities.entry(host.to_string()).or_default();
+                if !caps
+                    .iter()
+                    .any(|c| c.descriptor.id == *capid && c.binding_name == *instance_name)
+                {
+                    caps.push(HostedCapability {
+                        binding_name: instance_name.to_string(),
+                        descriptor: CapabilityDescriptor {
+                            id: capid.to_string(),
+                            ..Default::default()
+                        },
+                    });
+                }
+            }
+            BusEvent::ProviderRemoved {
+                capid,
+                instance_name,
+                host,
+            } => {
+                remove_where(&mut self.capabilities, host, |c| {
+                    c.descriptor.id == *capid && c.binding_name == *instance_name
+                });
+            }
+            BusEvent::ActorBindingCreated {
+                host,
+                actor,
+                capid,
+                instance_name,
+            } => {
+                self.track_host(host);
+                let bindings = self.bindings.entry(host.to_string()).or_default();
+                if !bindings
+                    .iter()
+                    .any(|b| binding_matches(b, actor, capid, instance_name))
+                {
+                    bindings.push(Binding {
+                        actor: actor.to_string(),
+                        capability_id: capid.to_string(),
+                        binding_name: instance_name.to_string(),
+                        configuration: HashMap::new(),
+                    });
+                }
+            }
+            BusEvent::ActorBindingRemoved {
+                host,
+                actor,
+                capid,
+                instance_name,
+            } => {
+                remove_where(&mut self.bindings, host, |b| {
+                    binding_matches(b, actor, capid, instance_name)
+                });
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the IDs of all hosts currently running the given actor
+    pub fn hosts_running_actor(&self, actor: &str) -> Vec<String> {
+        self.actors
+            .iter()
+            .filter(|(_, actors)| actors.iter().any(|a| a.subject == actor))
+            .map(|(host, _)| host.to_string())
+            .collect()
+    }
+
+    /// Returns the number of actors running on the given host
+    pub fn actor_count(&self, host: &str) -> usize {
+        self.actors.get(host).map_or(0, Vec::len)
+    }
+
+    fn track_host(&mut self, host: &str) {
+        self.hosts
+            .entry(host.to_string())
+            .or_insert_with(|| HostProfile {
+                id: host.to_string(),
+                labels: HashMap::new(),
+                uptime_ms: 0,
+            });
+    }
+}
+
+/// A lattice view that is kept up to date in the background. The view is seeded by probing the lattice
+/// inventory, then updated incrementally as lattice events arrive, so queries never wait on the network.
+/// Events can be missed (e.g. while disconnected), so the view is also periodically replaced with a
+/// freshly loaded snapshot to correct any drift. Background updates stop within one re-sync interval of
+/// the state being dropped
+pub struct LatticeState {
+    snapshot: Arc<RwLock<LatticeSnapshot>>,
+    active: Arc<AtomicBool>,
+}
+
+impl LatticeState {
+    /// Seeds a new lattice view using the given client and starts keeping it up to date, re-synchronizing
+    /// with the lattice inventory on the given interval. Returns an error if the initial load fails
+    pub fn start(client: Client, resync_interval: Duration) -> Result<LatticeState> {
+        let (sender, receiver) = unbounded();
+        // Subscribe before seeding so no events are lost between the load and the first update
+        let sub = client.watch_events(sender)?;
+        let snapshot = Arc::new(RwLock::new(LatticeSnapshot::load(&client)?));
+        let active = Arc::new(AtomicBool::new(true));
+
+        let handler_snapshot = snapshot.clone();
+        let handler_active = active.clone();
+        thread::spawn(move || {
+            let _sub = sub;
+            let ticker = tick(resync_interval);
+            while handler_active.load(Ordering::SeqCst) {
+                crossbeam::select! {
+                    recv(receiver) -> msg => match msg {
+                        Ok(Ok(event)) => handler_snapshot.write().unwrap().apply(&event),
+                        Ok(Err(e)) => log::warn!("{}", e),
+                        Err(_) => break,
+                    },
+                    recv(ticker) -> _ => {
+                        let current = handler_snapshot.read().unwrap().clone();
+                        match current.reload(&client) {
+                            Ok(fresh) => *handler_snapshot.write().unwrap() = fresh,
+                            Err(e) => log::warn!("Lattice state re-sync failed: {}", e),
+                        }
+                    }
+                }
+            }
+            handler_active.store(false, Ordering::SeqCst);
+        });
+
+        Ok(LatticeState { snapshot, active })
+    }
+
+    /// Indicates whether the view is still being updated in the background
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// Returns a consistent copy of the entire lattice view
+    pub fn snapshot(&self) -> LatticeSnapshot {
+        self.snapshot.read().unwrap().clone()
+    }
+
+    /// Returns all known hosts
+    pub fn hosts(&self) -> Vec<HostProfile> {
+        self.snapshot
+            .read()
+            .unwrap()
+            .hosts
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Returns all known actors, keyed by host ID
+    pub fn actors(&self) -> HashMap<String, Vec<Claims<Actor>>> {
+        self.snapshot.read().unwrap().actors.clone()
+    }
+
+    /// Returns all known capabilities, keyed by host ID
+    pub fn capabilities(&self) -> HashMap<String, Vec<HostedCapability>> {
+        self.snapshot.read().unwrap().capabilities.clone()
+    }
+
+    /// Returns all known bindings, keyed by host ID
+    pub fn bindings(&self) -> HashMap<String, Vec<Binding>> {
+        self.snapshot.read().unwrap().bindings.clone()
+    }
+
+    /// Returns the IDs of all hosts currently running the given actor
+    pub fn hosts_running_actor(&self, actor: &str) -> Vec<String> {
+        self.snapshot.read().unwrap().hosts_running_actor(actor)
+    }
+
+    /// Returns the number of actors running on the given host
+    pub fn actor_count(&self, host: &str) -> usize {
+        self.snapshot.read().unwrap().actor_count(host)
+    }
+}
+
+impl Drop for LatticeState {
+    fn drop(&mut self) {
+        self.active.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Takes the per-host items from an inventory report, logging the replies that could not be decoded and
+/// keeping the previous entries for the hosts that sent them
+fn retain_undecoded<V: Clone>(
+    report: InventoryReport<HashMap<String, V>>,
+    previous: &HashMap<String, V>,
+) -> HashMap<String, V> {
+    let mut items = report.items;
+    for e in &report.errors {
+        log::warn!("{}", e);
+        if let Some(host) = e.host.as_ref() {
+            if let Some(entry) = previous.get(host) {
+                items
+                    .entry(host.to_string())
+                    .or_insert_with(|| entry.clone());
+            }
+        }
+    }
+    items
+}
+
+fn remove_where<T>(map: &mut HashMap<String, Vec<T>>, host: &str, predicate: impl Fn(&T) -> bool) {
+    if let Some(items) = map.get_mut(host) {
+        items.retain(|i| !predicate(i));
+        if items.is_empty() {
+            map.remove(host);
+        }
+    }
+}
+
+fn binding_matches(binding: &Binding, actor: &str, capid: &str, instance_name: &str) -> bool {
+    binding.actor == actor
+        && binding.capability_id == capid
+        && binding.binding_name == instance_name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{InventoryReplyError, LatticeError};
+
+    fn undecoded(host: Option<&str>) -> InventoryReplyError {
+        InventoryReplyError {
+            host: host.map(str::to_string),
+            subject: "_INBOX.probe".to_string(),
+            raw: b"{}".to_vec(),
+            source: LatticeError::Timeout,
+        }
+    }
+
+    fn previous() -> HashMap<String, Vec<u32>> {
+        vec![
+            ("Nbad".to_string(), vec![1, 2]),
+            ("Ngone".to_string(), vec![3]),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn actor_started(actor: &str, host: &str) -> BusEvent {
+        BusEvent::ActorStarted {
+            actor: actor.to_string(),
+            host: host.to_string(),
+        }
+    }
+
+    fn provider_loaded(host: &str) -> BusEvent {
+        BusEvent::ProviderLoaded {
+            capid: "wascc:http_server".to_string(),
+            instance_name: "default".to_string(),
+            host: host.to_string(),
+        }
+    }
+
+    fn binding_created(host: &str) -> BusEvent {
+        BusEvent::ActorBindingCreated {
+            host: host.to_string(),
+            actor: "Mecho".to_string(),
+            capid: "wascc:http_server".to_string(),
+            instance_name: "default".to_string(),
+        }
+    }
+
+    fn populated() -> LatticeSnapshot {
+        let mut snapshot = LatticeSnapshot::default();
+        for event in &[
+            actor_started("Mecho", "Nhost"),
+            actor_started("Mkv", "Nhost"),
+            provider_loaded("Nhost"),
+            binding_created("Nhost"),
+            actor_started("Mecho", "Nother"),
+        ] {
+            snapshot.apply(event);
+        }
+        snapshot
+    }
+
+    #[test]
+    fn applying_events_is_idempotent() {
+        let mut snapshot = populated();
+        let once = snapshot.clone();
+        for event in &[
+            actor_started("Mecho", "Nhost"),
+            provider_loaded("Nhost"),
+            binding_created("Nhost"),
+            BusEvent::HostStarted("Nhost".to_string()),
+        ] {
+            snapshot.apply(event);
+        }
+        assert_eq!(snapshot, once);
+
+        let stopped = BusEvent::HostStopped("Nother".to_string());
+        snapshot.apply(&stopped);
+        let after_stop = snapshot.clone();
+        snapshot.apply(&stopped);
+        assert_eq!(snapshot, after_stop);
+    }
+
+    #[test]
+    fn started_entities_get_placeholders() {
+        let snapshot = populated();
+
+        let host = &snapshot.hosts["Nhost"];
+        assert_eq!(host.id, "Nhost");
+        assert!(host.labels.is_empty());
+        let subjects: Vec<&str> = snapshot.actors["Nhost"]
+            .iter()
+            .map(|a| a.subject.as_str())
+            .collect();
+        assert_eq!(subjects, vec!["Mecho", "Mkv"]);
+        let cap = &snapshot.capabilities["Nhost"][0];
+        assert_eq!(cap.descriptor.id, "wascc:http_server");
+        assert_eq!(cap.binding_name, "default");
+        let binding = &snapshot.bindings["Nhost"][0];
+        assert_eq!(binding.actor, "Mecho");
+        assert!(binding.configuration.is_empty());
+    }
+
+    #[test]
+    fn host_stopped_clears_every_map() {
+        let mut snapshot = populated();
+        snapshot.apply(&BusEvent::HostStopped("Nhost".to_string()));
+
+        assert!(!snapshot.hosts.contains_key("Nhost"));
+        assert!(!snapshot.actors.contains_key("Nhost"));
+        assert!(!snapshot.capabilities.contains_key("Nhost"));
+        assert!(!snapshot.bindings.contains_key("Nhost"));
+        assert_eq!(snapshot.hosts_running_actor("Mecho"), vec!["Nother"]);
+    }
+
+    #[test]
+    fn actor_stopped_removes_only_that_actor() {
+        let mut snapshot = populated();
+        snapshot.apply(&BusEvent::ActorStopped {
+            actor: "Mecho".to_string(),
+            host: "Nhost".to_string(),
+        });
+        assert_eq!(snapshot.actor_count("Nhost"), 1);
+        assert_eq!(snapshot.actors["Nhost"][0].subject, "Mkv");
+
+        snapshot.apply(&BusEvent::ActorStopped {
+            actor: "Mkv".to_string(),
+            host: "Nhost".to_string(),
+        });
+        assert!(!snapshot.actors.contains_key("Nhost"));
+        // The host itself is still known until it stops
+        assert!(snapshot.hosts.contains_key("Nhost"));
+    }
+
+    #[test]
+    fn provider_removed_drops_the_host_entry() {
+        let mut snapshot = populated();
+        snapshot.apply(&BusEvent::ProviderRemoved {
+            capid: "wascc:http_server".to_string(),
+            instance_name: "other".to_string(),
+            host: "Nhost".to_string(),
+        });
+        assert_eq!(snapshot.capabilities["Nhost"].len(), 1);
+
+        snapshot.apply(&BusEvent::ProviderRemoved {
+            capid: "wascc:http_server".to_string(),
+            instance_name: "default".to_string(),
+            host: "Nhost".to_string(),
+        });
+        assert!(!snapshot.capabilities.contains_key("Nhost"));
+    }
+
+    #[test]
+    fn binding_removed_drops_the_host_entry() {
+        let mut snapshot = populated();
+        snapshot.apply(&BusEvent::ActorBindingRemoved {
+            host: "Nhost".to_string(),
+            actor: "Mecho".to_string(),
+            capid: "wascc:http_server".to_string(),
+            instance_name: "default".to_string(),
+        });
+        assert!(!snapshot.bindings.contains_key("Nhost"));
+    }
+
+    #[test]
+    fn undecoded_hosts_keep_their_previous_entries() {
+        let report = InventoryReport {
+            items: vec![("Ngood".to_string(), vec![4])].into_iter().collect(),
+            errors: vec![undecoded(Some("Nbad")), undecoded(None)],
+        };
+        let items = retain_undecoded(report, &previous());
+        assert_eq!(items.get("Ngood"), Some(&vec![4]));
+        assert_eq!(items.get("Nbad"), Some(&vec![1, 2]));
+        // Hosts that did not reply at all are dropped, as they would be by a full load
+        assert_eq!(items.get("Ngone"), None);
+    }
+}
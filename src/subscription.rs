@@ -0,0 +1,75 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::thread;
+
+use crossbeam::Sender;
+
+use crate::events::decode_event;
+use crate::{BusEvent, EventDecodeError, EventFilter, Result};
+
+/// A handle to an active lattice event subscription. Events are delivered to a channel by a
+/// background thread until the subscription is cancelled, either explicitly through
+/// [unsubscribe](#method.unsubscribe) or implicitly when the handle is dropped. The subscription
+/// also ends on its own if the receiving half of the channel is dropped or the connection closes
+pub struct EventSubscription {
+    sub: Option<nats::Subscription>,
+    active: Arc<AtomicBool>,
+}
+
+impl EventSubscription {
+    pub(crate) fn start(
+        sub: nats::Subscription,
+        filter: EventFilter,
+        sender: Sender<std::result::Result<BusEvent, EventDecodeError>>,
+    ) -> EventSubscription {
+        let active = Arc::new(AtomicBool::new(true));
+        let handler_sub = sub.clone();
+        let handler_active = active.clone();
+        thread::spawn(move || {
+            for msg in handler_sub.iter() {
+                let decoded = decode_event(&msg.data);
+                if let Ok(ref event) = decoded {
+                    if !filter.matches(event) {
+                        continue;
+                    }
+                }
+                if sender.send(decoded).is_err() {
+                    break;
+                }
+            }
+            handler_active.store(false, Ordering::SeqCst);
+            let _ = handler_sub.unsubscribe();
+        });
+        EventSubscription {
+            sub: Some(sub),
+            active,
+        }
+    }
+
+    /// Indicates whether the subscription is still delivering events
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// Cancels the subscription. Any events already received but not yet delivered are discarded
+    pub fn unsubscribe(mut self) -> Result<()> {
+        self.cancel()
+    }
+
+    fn cancel(&mut self) -> Result<()> {
+        if let Some(sub) = self.sub.take() {
+            if self.active.swap(false, Ordering::SeqCst) {
+                sub.unsubscribe()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for EventSubscription {
+    fn drop(&mut self) {
+        let _ = self.cancel();
+    }
+}